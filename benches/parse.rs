@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+const IDAT_SIZE: usize = 16 * 1024 * 1024;
+
+fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data).unwrap()
+}
+
+/// A PNG whose image data is one large `IDAT` chunk of arbitrary bytes.
+fn large_png() -> Vec<u8> {
+    let idat = (0..IDAT_SIZE).map(|i| i as u8).collect();
+    Png::from_chunks(vec![
+        chunk("IHDR", vec![0, 0, 16, 0, 0, 0, 4, 0, 8, 0, 0, 0, 0]),
+        chunk("IDAT", idat),
+        chunk("IEND", vec![]),
+    ])
+    .as_bytes()
+}
+
+fn parse_png(c: &mut Criterion) {
+    let bytes = large_png();
+    let mut group = c.benchmark_group("png");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| Png::from_bytes(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+fn parse_chunk(c: &mut Criterion) {
+    let bytes = chunk("IDAT", vec![0xAB; IDAT_SIZE]).as_bytes();
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("try_from", |b| {
+        b.iter(|| Chunk::try_from(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_png, parse_chunk);
+criterion_main!(benches);
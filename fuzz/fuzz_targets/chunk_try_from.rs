@@ -0,0 +1,14 @@
+//! Feeds arbitrary bytes to `Chunk::try_from`, which must return `Err` for
+//! bad input rather than panic. Run from the repository root with
+//!
+//! ```text
+//! cargo +nightly fuzz run chunk_try_from fuzz/corpus/chunk_try_from
+//! ```
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk::Chunk;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Chunk::try_from(&data.to_vec());
+});
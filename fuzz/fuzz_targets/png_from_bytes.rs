@@ -0,0 +1,15 @@
+//! Feeds arbitrary bytes to `Png::from_bytes`, which must return `Err` for
+//! bad input rather than panic. Run from the repository root with
+//!
+//! ```text
+//! cargo install cargo-fuzz
+//! cargo +nightly fuzz run png_from_bytes fuzz/corpus/png_from_bytes
+//! ```
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::png::Png;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Png::from_bytes(data);
+});
@@ -1,37 +1,646 @@
-use clap::{Parser, Args, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use crate::logging::Verbosity;
+use crate::png::ParseOptions;
 
 #[derive(Parser, Debug)]
 #[command()]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Print only essential output, such as decoded text
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print per-chunk diagnostics to stderr while reading files
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+    /// Show how far reads and writes of large files have got, when stderr
+    /// is a terminal
+    #[arg(long, global = true)]
+    pub progress: bool,
+}
+
+impl DecodeArgs {
+    /// The output format, with the --base64 and --raw shorthands applied.
+    pub fn output_format(&self) -> DecodeFormat {
+        if self.raw {
+            DecodeFormat::Raw
+        } else if self.base64 {
+            DecodeFormat::Base64
+        } else {
+            self.format
+        }
+    }
+}
+
+impl Cli {
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
+/// When to color terminal output.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How decode writes out a chunk's data.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeFormat {
+    /// UTF-8 text followed by a newline; invalid UTF-8 is an error
+    #[default]
+    Text,
+    /// One lowercase hex string
+    Hex,
+    /// Standard Base64
+    Base64,
+    /// The bytes as stored, with no newline added
+    Raw,
+}
+
+/// How message text maps to chunk bytes.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// One byte per character, covering only the first 256 code points
+    Latin1,
+}
+
+/// What the read commands expect to find in their input.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    /// A PNG file, starting with the PNG signature
+    #[default]
+    Png,
+    /// Chunks in the PNG layout with no signature in front of them
+    Chunks,
+}
+
+impl InputFormat {
+    /// The parse options for this format, starting from `options`.
+    pub fn parse_options(self, options: ParseOptions) -> ParseOptions {
+        ParseOptions {
+            expect_signature: self == InputFormat::Png,
+            ..options
+        }
+    }
+}
+
+/// Digest the hash command prints.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// The CRC-32 variant PNG chunks use
+    Crc32,
+}
+
+/// The option of every command that can read a bare chunk stream.
+#[derive(Args, Debug, Default)]
+pub struct InputFormatArgs {
+    /// Read a PNG file, or a bare chunk stream with no signature
+    #[arg(
+        long = "input-format",
+        id = "input_format",
+        value_name = "INPUT_FORMAT",
+        value_enum,
+        default_value_t = InputFormat::Png
+    )]
+    pub format: InputFormat,
+}
+
+/// The option of every command that can read chunks over the length limit.
+#[derive(Args, Debug, Default)]
+pub struct OversizeArgs {
+    /// Read chunks that claim more than the PNG maximum of 2^31-1 bytes,
+    /// with a warning, instead of refusing the file
+    #[arg(long)]
+    pub allow_oversize: bool,
+}
+
+/// The options of every command that can edit a file in place.
+#[derive(Args, Debug, Default)]
+pub struct BackupArgs {
+    /// Before editing a file in place, copy it to its name plus SUFFIX,
+    /// which defaults to .bak
+    #[arg(
+        long = "backup",
+        id = "backup",
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        default_missing_value = ".bak"
+    )]
+    pub suffix: Option<String>,
+    /// Replace an existing backup file instead of refusing to
+    #[arg(long, requires = "backup")]
+    pub force: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    Encode(EncodeArgs),
+    Encode(Box<EncodeArgs>),
     Decode(DecodeArgs),
     Remove(RemoveArgs),
-    Print(PrintArgs)
+    /// Insert a message chunk at a given position in the chunk list
+    Insert(InsertArgs),
+    Print(PrintArgs),
+    /// Compare the chunk lists of two PNG files
+    Diff(DiffArgs),
+    /// Remove every ancillary chunk, keeping only the image itself
+    Strip(StripArgs),
+    /// Copy ancillary chunks of the given types from one PNG file into another
+    Merge(MergeArgs),
+    /// Summarize a PNG file's image header and chunks
+    Info(InfoArgs),
+    /// List the chunks of a PNG file
+    List(ListArgs),
+    /// Explain the bytes and property bits of a chunk type
+    Typeinfo(TypeinfoArgs),
+    /// List the standard PNG chunk types and what each is for
+    ChunkTypes(ChunkTypesArgs),
+    /// Suggest a private chunk type, explain what its letters mean and show
+    /// how to hide a message with it
+    Quickstart(QuickstartArgs),
+    /// Count the chunks of each type in a PNG file
+    Count(CountArgs),
+    /// Write a chunk's raw data to a file
+    Extract(ExtractArgs),
+    /// Check every chunk's stored CRC against its data
+    Verify(VerifyArgs),
+    /// Rewrite every chunk's CRC to match its data
+    Fix(FixArgs),
+    /// Rewrite a PNG file in canonical form: chunks in the specification's
+    /// order, correct CRCs and nothing after IEND
+    Normalize(NormalizeArgs),
+    /// Change the type of every chunk of one type, keeping its data
+    Relabel(RelabelArgs),
+    /// Print a content hash of a PNG file
+    Hash(HashArgs),
+    /// Write every chunk of a PNG file to a signature-less chunk archive
+    Archive(ArchiveArgs),
+    /// Rebuild a PNG file from a chunk archive
+    Unarchive(UnarchiveArgs),
+    /// Look for whole PNG files hidden inside chunk data
+    Scan(ScanArgs),
+    /// Show how random each chunk's data looks, in bits per byte
+    Entropy(EntropyArgs),
 }
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
+#[command(group(
+    ArgGroup::new("source")
+        .required(true)
+        .args(["message", "message_file", "message_stdin", "spec", "pack"])
+))]
 pub struct EncodeArgs {
-    filepath: String,
-    chunk_type: String,
-    message: String,
-    output: Option<String>
+    /// PNG file to encode into, or - to read it from standard input and,
+    /// without an output, write the result to standard output
+    pub filepath: String,
+    #[arg(
+        required_unless_present_any = ["spec", "auto_type"],
+        conflicts_with_all = ["spec", "auto_type"]
+    )]
+    pub chunk_type: Option<String>,
+    pub message: Option<String>,
+    pub output: Option<String>,
+    /// Without an explicit output, write to this directory as the input's
+    /// file name with a .stego.png suffix, creating the directory if needed
+    #[arg(long)]
+    pub output_dir: Option<String>,
+    /// Read the message bytes from a file
+    #[arg(long)]
+    pub message_file: Option<String>,
+    /// Read the message bytes from standard input
+    #[arg(long)]
+    pub message_stdin: bool,
+    /// Encode every chunk listed in a JSON spec file, in order
+    #[arg(long)]
+    pub spec: Option<String>,
+    /// Store a named message, given as name=message, alongside any others
+    /// in the same chunk; may be given more than once
+    #[arg(long, value_name = "NAME=MESSAGE", conflicts_with = "base64")]
+    pub pack: Vec<String>,
+    /// Pick an unused private chunk type instead of taking one as an
+    /// argument; the message must then come from --message-file or
+    /// --message-stdin
+    #[arg(long, conflicts_with = "spec")]
+    pub auto_type: bool,
+    /// Seed for --auto-type so the chosen type is repeatable
+    #[arg(long, env = "PNGME_SEED")]
+    pub seed: Option<u64>,
+    /// Treat the message as Base64 and store the decoded bytes
+    #[arg(long)]
+    pub base64: bool,
+    /// How the message text is stored: UTF-8, or Latin-1 as tEXt chunks
+    /// use; not allowed with --base64 or --pack
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = TextEncoding::Utf8,
+        conflicts_with_all = ["base64", "pack"]
+    )]
+    pub encoding: TextEncoding,
+    /// Put the message's length and a truncated SHA-256 in front of it, so
+    /// decode --verified can detect tampering
+    #[arg(long, conflicts_with = "spec")]
+    pub verified: bool,
+    /// Also add N chunks of the same type and length filled with random
+    /// bytes, with the real one at a random place among them; decode
+    /// --verified skips the decoys. Uses --seed when given
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        requires = "verified",
+        conflicts_with_all = ["split", "replace", "compress"]
+    )]
+    pub decoys: usize,
+    /// Write these 8 bytes, given as 16 hex digits, instead of the PNG
+    /// signature. The output is then not a valid PNG; this is for testing
+    /// other parsers
+    #[arg(long, value_name = "HEX")]
+    pub signature: Option<String>,
+    /// Print the steps encode would take and exit without doing them
+    #[arg(long)]
+    pub plan: bool,
+    /// Refuse chunk types that break the PNG naming rules instead of warning
+    #[arg(long)]
+    pub strict: bool,
+    /// Store the message as zlib-compressed text, in the zTXt layout
+    #[arg(long)]
+    pub compress: bool,
+    /// Keyword written in front of compressed text
+    #[arg(long, default_value = "Comment", requires = "compress")]
+    pub keyword: String,
+    /// Compression method byte written in front of compressed text
+    #[arg(long, default_value_t = 0, requires = "compress")]
+    pub compression_method: u8,
+    /// Discard any data after the IEND chunk instead of keeping it
+    #[arg(long)]
+    pub drop_trailing: bool,
+    /// Spread the message over as many chunks as needed, each holding at
+    /// most this many bytes; decode them with --join
+    #[arg(long, value_name = "BYTES", conflicts_with = "spec")]
+    pub split: Option<usize>,
+    /// Overwrite the first existing chunk of the same type in place instead
+    /// of adding another one
+    #[arg(long, conflicts_with = "split")]
+    pub replace: bool,
+    /// Add the message to the end of the first existing chunk of the same
+    /// type, creating the chunk only if there is none
+    #[arg(
+        long,
+        conflicts_with_all = ["replace", "split", "verified", "compress"]
+    )]
+    pub append_to_existing: bool,
+    /// Put the new chunk straight after the last chunk of this type, such as
+    /// PLTE, instead of just before IEND
+    #[arg(long, value_name = "TYPE", conflicts_with_all = ["spec", "split", "decoys"])]
+    pub after: Option<String>,
+    /// Store the message even if an identical chunk is already there
+    #[arg(long)]
+    pub allow_duplicate: bool,
+    /// Show what would change without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub backup: BackupArgs,
 }
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 pub struct DecodeArgs {
-    filepath: String,
-    chunk_type: String,
+    pub filepath: String,
+    /// Chunk type to decode, matched case-sensitively since the case of each
+    /// letter is part of the type
+    pub chunk_type: String,
+    /// Match any chunk type with the same letters, whatever their case
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// How to write out the chunk data
+    #[arg(long, value_enum, default_value_t = DecodeFormat::Text)]
+    pub format: DecodeFormat,
+    /// Shorthand for --format base64
+    #[arg(long, conflicts_with = "format")]
+    pub base64: bool,
+    /// How --format text reads the data: as UTF-8, or as Latin-1 as tEXt
+    /// chunks use
+    #[arg(long, value_enum, default_value_t = TextEncoding::Utf8)]
+    pub encoding: TextEncoding,
+    /// Inflate chunk data stored in the zTXt layout before printing it
+    #[arg(long)]
+    pub decompress: bool,
+    /// Shorthand for --format raw
+    #[arg(long, conflicts_with_all = ["base64", "format"])]
+    pub raw: bool,
+    /// Reassemble a message written with encode --split from every chunk of
+    /// this type
+    #[arg(long)]
+    pub join: bool,
+    /// Check and remove the header written by encode --verified, failing if
+    /// the message was cut short or modified
+    #[arg(long)]
+    pub verified: bool,
+    /// Print only the named message from a chunk written with encode --pack
+    #[arg(long, value_name = "NAME")]
+    pub unpack: Option<String>,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+    #[command(flatten)]
+    pub oversize: OversizeArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct InsertArgs {
+    pub filepath: String,
+    pub chunk_type: String,
+    pub message: String,
+    /// Position the new chunk takes, counting from 0; it must fall after
+    /// IHDR and no later than IEND
+    #[arg(long)]
+    pub index: usize,
+    #[command(flatten)]
+    pub backup: BackupArgs,
 }
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 pub struct RemoveArgs {
-    filepath: String,
-    chunk_type: String,
+    /// PNG file to edit, or - to read it from standard input and write the
+    /// result to standard output
+    pub filepath: String,
+    /// Chunk types to remove, as separate arguments or comma-separated;
+    /// every chunk of each type is removed, matching case-sensitively
+    #[arg(required = true, num_args = 1.., value_delimiter = ',')]
+    pub chunk_types: Vec<String>,
+    /// Also remove chunks whose type has the same letters in another case
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// Discard any data after the IEND chunk instead of keeping it
+    #[arg(long)]
+    pub drop_trailing: bool,
+    /// Show what would change without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub backup: BackupArgs,
 }
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 pub struct PrintArgs {
-    filepath: String,
-}
\ No newline at end of file
+    /// Files to read, or - for standard input; each gets a header when more
+    /// than one is given
+    #[arg(required = true, num_args = 1..)]
+    pub filepaths: Vec<String>,
+    /// Print the chunks as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Print each chunk's data as a hex dump
+    #[arg(long, conflicts_with = "json")]
+    pub hex: bool,
+    /// Report any data after the IEND chunk, with a hex preview
+    #[arg(long, conflicts_with = "json")]
+    pub show_trailing: bool,
+    /// Show only ancillary chunks, where metadata and messages live
+    #[arg(long, conflicts_with = "only_critical")]
+    pub only_ancillary: bool,
+    /// Show only the critical chunks that make up the image
+    #[arg(long)]
+    pub only_critical: bool,
+    /// Show only chunks whose four-letter type matches this regular
+    /// expression, such as '^[a-z]' for ancillary types
+    #[arg(long, value_name = "REGEX")]
+    pub type_filter: Option<String>,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+    #[command(flatten)]
+    pub oversize: OversizeArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct DiffArgs {
+    pub left: String,
+    pub right: String,
+}
+#[derive(Args, Debug, Default)]
+pub struct StripArgs {
+    pub filepath: String,
+    /// Where to write the stripped file; defaults to overwriting the input
+    pub output: Option<String>,
+    /// Ancillary chunk type to keep; may be given more than once
+    #[arg(long)]
+    pub keep: Vec<String>,
+    /// Discard any data after the IEND chunk instead of keeping it
+    #[arg(long)]
+    pub drop_trailing: bool,
+    /// Show what would change without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct MergeArgs {
+    /// File to copy chunks from
+    pub source: String,
+    /// File to copy them into, before its IEND chunk
+    pub destination: String,
+    /// Where to write the merged file; defaults to overwriting the destination
+    pub output: Option<String>,
+    /// Ancillary chunk types to copy, as separate arguments or
+    /// comma-separated; every chunk of each type is copied, in order
+    #[arg(long, required = true, num_args = 1.., value_delimiter = ',')]
+    pub types: Vec<String>,
+    /// Show what would change without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct InfoArgs {
+    pub filepath: String,
+    /// Report any data after the IEND chunk, with a hex preview
+    #[arg(long)]
+    pub show_trailing: bool,
+    /// Color critical and ancillary chunks differently
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+#[derive(Args, Debug, Default)]
+pub struct ListArgs {
+    /// Files to read; each gets a header when more than one is given
+    #[arg(required = true, num_args = 1..)]
+    pub filepaths: Vec<String>,
+    /// Read up to N files at once; output still follows the order the files
+    /// were given in, though warnings may not
+    #[arg(long, short, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+    /// List the chunks as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Color critical and ancillary chunks differently, and bad CRCs red
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    /// Show only ancillary chunks, where metadata and messages live
+    #[arg(long, conflicts_with = "only_critical")]
+    pub only_ancillary: bool,
+    /// Show only the critical chunks that make up the image
+    #[arg(long)]
+    pub only_critical: bool,
+    /// Show only chunks whose four-letter type matches this regular
+    /// expression, such as '^[a-z]' for ancillary types
+    #[arg(long, value_name = "REGEX")]
+    pub type_filter: Option<String>,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+    #[command(flatten)]
+    pub oversize: OversizeArgs,
+    /// When a chunk can't be parsed, skip ahead to the next one that can
+    /// instead of giving up
+    #[arg(long)]
+    pub recover: bool,
+}
+#[derive(Args, Debug, Default)]
+pub struct TypeinfoArgs {
+    pub chunk_type: String,
+}
+#[derive(Args, Debug, Default)]
+pub struct ChunkTypesArgs {}
+#[derive(Args, Debug, Default)]
+pub struct QuickstartArgs {
+    /// PNG file to hide the example message in, editing it in place; without
+    /// one, only the commands to run are printed
+    pub filepath: Option<String>,
+    /// Message to hide
+    #[arg(long, default_value = "hello from pngme")]
+    pub message: String,
+    /// Seed for the suggested type so it is repeatable
+    #[arg(long, env = "PNGME_SEED")]
+    pub seed: Option<u64>,
+}
+#[derive(Args, Debug, Default)]
+pub struct CountArgs {
+    pub filepath: String,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct ExtractArgs {
+    pub filepath: String,
+    pub chunk_type: String,
+    pub output: String,
+    /// Which chunk of this type to extract, counting from 0
+    #[arg(long, default_value_t = 0)]
+    pub index: usize,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+    #[command(flatten)]
+    pub oversize: OversizeArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct ArchiveArgs {
+    pub filepath: String,
+    pub output: String,
+    /// Number of bytes to skip before the PNG signature
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+    #[command(flatten)]
+    pub input: InputFormatArgs,
+    #[command(flatten)]
+    pub oversize: OversizeArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct UnarchiveArgs {
+    pub archive: String,
+    pub output: String,
+}
+#[derive(Args, Debug, Default)]
+pub struct ScanArgs {
+    pub filepath: String,
+    /// How many levels of PNGs inside PNGs to look through
+    #[arg(long, default_value_t = 3)]
+    pub max_depth: usize,
+}
+#[derive(Args, Debug, Default)]
+pub struct EntropyArgs {
+    pub filepath: String,
+    /// Flag chunks whose data has more bits of entropy per byte than this
+    #[arg(long, default_value_t = 7.5)]
+    pub threshold: f64,
+}
+#[derive(Args, Debug, Default)]
+pub struct VerifyArgs {
+    /// Files to read; each gets a header when more than one is given
+    #[arg(required = true, num_args = 1..)]
+    pub filepaths: Vec<String>,
+    /// Read up to N files at once; output still follows the order the files
+    /// were given in, though warnings may not
+    #[arg(long, short, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+    /// Print one line per file, "pass", "fail" or "unreadable" then the
+    /// path and the first problem, then a summary; exit with 1 if any file
+    /// failed and 2 if any couldn't be read. A directory stands for the
+    /// *.png files directly inside it
+    #[arg(long, conflicts_with = "jobs")]
+    pub check_only: bool,
+}
+#[derive(Args, Debug, Default)]
+pub struct FixArgs {
+    pub filepath: String,
+    pub output: String,
+    /// When a chunk can't be parsed, skip ahead to the next one that can
+    /// instead of giving up
+    #[arg(long)]
+    pub recover: bool,
+}
+#[derive(Args, Debug, Default)]
+pub struct RelabelArgs {
+    pub filepath: String,
+    /// Chunk type to relabel, matched case-sensitively
+    pub old_type: String,
+    /// Chunk type to give those chunks
+    pub new_type: String,
+    /// Show what would change without writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+#[derive(Args, Debug, Default)]
+pub struct HashArgs {
+    pub filepath: String,
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+    pub algorithm: HashAlgorithm,
+    /// Hash the file as normalize would write it, so chunk order, CRC and
+    /// trailing data differences don't change the hash
+    #[arg(long)]
+    pub normalize: bool,
+}
+#[derive(Args, Debug, Default)]
+pub struct NormalizeArgs {
+    pub filepath: String,
+    /// Where to write the normalized file; defaults to overwriting the input
+    pub output: Option<String>,
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::png::Png;
+use crate::Result;
+
+/// In-process cache of parsed PNG files, keyed by path.
+///
+/// A cached entry is reused for as long as the file's modification time stays
+/// the same; once it changes the file is read and parsed again.
+#[derive(Default)]
+pub struct PngCache {
+    entries: Mutex<HashMap<PathBuf, (SystemTime, Arc<Png>)>>,
+    reads: AtomicUsize,
+}
+
+impl PngCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_parse(&self, path: impl AsRef<Path>) -> Result<Arc<Png>> {
+        let path = path.as_ref();
+        let modified = fs::metadata(path)?.modified()?;
+
+        let mut entries = self.entries.lock().map_err(|_| "png cache lock poisoned")?;
+        if let Some((cached_modified, png)) = entries.get(path) {
+            if *cached_modified == modified {
+                return Ok(Arc::clone(png));
+            }
+        }
+
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let bytes = fs::read(path)?;
+        let png = Arc::new(Png::try_from(bytes.as_slice())?);
+        entries.insert(path.to_path_buf(), (modified, Arc::clone(&png)));
+
+        Ok(png)
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn write_png(path: &Path, message: &str) {
+        let chunk = Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            message.as_bytes().to_vec(),
+        )
+        .unwrap();
+        fs::write(path, Png::from_chunks(vec![chunk]).as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_unchanged_file_is_not_reread() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cached.png");
+        write_png(&path, "first");
+
+        let cache = PngCache::new();
+        let first = cache.get_or_parse(&path).unwrap();
+        let second = cache.get_or_parse(&path).unwrap();
+
+        assert_eq!(cache.reads.load(Ordering::Relaxed), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_modified_file_is_reread() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cached.png");
+        write_png(&path, "first");
+
+        let cache = PngCache::new();
+        cache.get_or_parse(&path).unwrap();
+
+        write_png(&path, "second");
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        let png = cache.get_or_parse(&path).unwrap();
+        assert_eq!(cache.reads.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "second"
+        );
+    }
+}
@@ -1,37 +1,76 @@
 use crate::chunk_type::ChunkType;
-use crate::{Error, Result};
+use crate::png::ParseOptions;
+use crate::{Error, PngError, Result};
 use crc::{Crc, CRC_32_ISO_HDLC};
-use std::{
-    fmt,
-    io::{BufReader, Read},
-};
+use std::fmt;
+use std::io::{self, Read};
 
-const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+/// The CRC-32 variant the PNG specification uses, and the default everywhere.
+pub static PNG_CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
+/// A single PNG chunk.
+///
+/// The stored length and CRC always describe the data. Edits made through
+/// `data_mut` go to a separate copy that only replaces the data, with a new
+/// length and CRC, once `commit` has checked its length.
 #[derive(Debug)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
     data: Vec<u8>,
     crc: u32,
+    crc_valid: bool,
+    /// Data edited through `data_mut` and not yet committed
+    pending: Option<Vec<u8>>,
 }
 
 impl Chunk {
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        let crc = calculate_crc(&chunk_type, &data);
+    /// Largest data length the PNG specification allows for a chunk (2^31 - 1).
+    pub const MAX_LENGTH: u32 = 0x7FFF_FFFF;
 
-        Chunk {
-            length: data.len() as u32,
+    /// Builds a chunk with the length and CRC computed from `data`, which must
+    /// not be longer than `MAX_LENGTH`.
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Self> {
+        Chunk::new_with_algorithm(chunk_type, data, &PNG_CRC)
+    }
+
+    /// Like `new`, but computes the CRC with `crc` instead of the PNG CRC-32,
+    /// for containers that reuse the chunk layout with a different checksum.
+    pub fn new_with_algorithm(
+        chunk_type: ChunkType,
+        data: Vec<u8>,
+        crc: &Crc<u32>,
+    ) -> Result<Self> {
+        let length = checked_length(data.len())?;
+        let crc = checksum(crc, &chunk_type, &data);
+
+        Ok(Chunk {
+            length,
             chunk_type,
             data,
             crc,
+            crc_valid: true,
+            pending: None,
+        })
+    }
+
+    /// Builds a chunk with no data, such as IEND, in a `const` context.
+    pub const fn empty(chunk_type: ChunkType) -> Chunk {
+        let crc = PNG_CRC.checksum(&chunk_type.bytes());
+        Chunk {
+            length: 0,
+            chunk_type,
+            data: Vec::new(),
+            crc,
+            crc_valid: true,
+            pending: None,
         }
     }
 
     pub fn data_as_string(&self) -> Result<String> {
         let data = self.data.clone();
         match String::from_utf8(data) {
-            Ok(data_string) => return Ok(data_string),
+            Ok(data_string) => Ok(data_string),
             Err(_) => Err("not valid utf8")?,
         }
     }
@@ -39,10 +78,10 @@ impl Chunk {
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
-        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.length().to_be_bytes());
         bytes.extend_from_slice(&self.chunk_type.bytes());
         bytes.extend_from_slice(&self.data);
-        bytes.extend_from_slice(&self.crc.to_be_bytes());
+        bytes.extend_from_slice(&self.crc().to_be_bytes());
 
         bytes
     }
@@ -55,55 +94,211 @@ impl Chunk {
         &self.chunk_type
     }
 
+    /// The chunk type as text, such as `"IDAT"`.
+    pub fn type_str(&self) -> String {
+        self.chunk_type.to_string()
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.data
     }
 
+    /// A reader over the data, borrowing it rather than copying it.
+    pub fn read_data(&self) -> impl Read + '_ {
+        io::Cursor::new(self.data.as_slice())
+    }
+
+    /// Mutable access to a copy of the data for editing. The chunk keeps its
+    /// current data, length and CRC until `commit` takes the edits in.
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        self.pending.get_or_insert_with(|| self.data.clone())
+    }
+
+    /// Replaces the data with the edits made through `data_mut`, computing
+    /// the length and CRC for it, as `set_data` does. Fails, keeping the
+    /// edits pending, if the edited data is longer than `MAX_LENGTH`.
+    pub fn commit(&mut self) -> Result<()> {
+        let Some(data) = self.pending.take() else {
+            return Ok(());
+        };
+        if let Err(e) = checked_length(data.len()) {
+            self.pending = Some(data);
+            return Err(e);
+        }
+        self.set_data(data)
+    }
+
     pub fn crc(&self) -> u32 {
         self.crc
     }
+
+    /// Whether the stored CRC matched the data when the chunk was built.
+    /// Strict parsing never produces a chunk for which this is false.
+    pub fn crc_ok(&self) -> bool {
+        self.crc_valid
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.chunk_type.is_critical()
+    }
+
+    pub fn is_ancillary(&self) -> bool {
+        !self.chunk_type.is_critical()
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.chunk_type.is_public()
+    }
+
+    pub fn is_safe_to_copy(&self) -> bool {
+        self.chunk_type.is_safe_to_copy()
+    }
+
+    /// Replaces the chunk data, updating the length and CRC to match it and
+    /// dropping any uncommitted edits.
+    pub fn set_data(&mut self, data: Vec<u8>) -> Result<()> {
+        self.length = checked_length(data.len())?;
+        self.crc = calculate_crc(&self.chunk_type, &data);
+        self.crc_valid = true;
+        self.pending = None;
+        self.data = data;
+        Ok(())
+    }
+
+    /// The CRC the PNG specification requires for a chunk with this type and data.
+    pub fn crc_of(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        calculate_crc(chunk_type, data)
+    }
+
+    /// Recomputes the stored CRC from the current type and data, repairing a
+    /// chunk whose CRC no longer matches.
+    pub fn recalculate_crc(&mut self) {
+        self.crc = calculate_crc(&self.chunk_type, &self.data);
+        self.crc_valid = true;
+    }
+
+    /// Returns this chunk relabelled as `new_type`, keeping its data and
+    /// computing the CRC for the new type.
+    pub fn with_type(mut self, new_type: ChunkType) -> Chunk {
+        self.chunk_type = new_type;
+        self.recalculate_crc();
+        self
+    }
+
+    /// Returns this chunk with a stored CRC that is guaranteed not to match its
+    /// data, for building corrupt fixtures.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_corrupt_crc(mut self) -> Chunk {
+        self.crc = !calculate_crc(&self.chunk_type, &self.data);
+        self.crc_valid = false;
+        self
+    }
+
+    /// Builds a chunk that stores `crc` as-is instead of computing it from the
+    /// data, so it bypasses the integrity check every other constructor
+    /// guarantees. Only for building corrupt-input fixtures.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn new_with_crc(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Result<Self> {
+        Ok(Chunk {
+            length: checked_length(data.len())?,
+            crc_valid: crc == calculate_crc(&chunk_type, &data),
+            chunk_type,
+            data,
+            crc,
+            pending: None,
+        })
+    }
+
+    /// Number of bytes this chunk takes up in a file: length, type, data and CRC.
+    pub fn serialized_len(&self) -> usize {
+        12 + self.data.len()
+    }
 }
 
+/// Parses `bytes` as exactly one chunk. A length field that disagrees with
+/// the data given is reported as such before the CRC is checked, since the
+/// CRC would otherwise be read from the wrong place and merely mismatch.
 impl TryFrom<&Vec<u8>> for Chunk {
     type Error = Error;
     fn try_from(bytes: &Vec<u8>) -> Result<Self> {
-        if bytes.len() < 12 {
-            Err("Invalid chunk length")?;
+        if let Some(given) = bytes.len().checked_sub(12) {
+            let length = usize::try_from(u32::from_be_bytes(bytes[0..4].try_into()?))?;
+            if length < given {
+                Err(format!(
+                    "chunk length {} doesn't match the {} data bytes given",
+                    length, given
+                ))?;
+            }
         }
+        Chunk::from_slice(bytes, &ParseOptions::default())
+    }
+}
 
-        // Create buffer reader and buffers for length and chunk type
-        let mut reader = BufReader::new(&bytes[..]);
-        let mut l_buf: [u8; 4] = [0; 4];
-        let mut ct_buf: [u8; 4] = [0; 4];
-
-        // Read values for length and chunk type off the front of the buffer
-        reader.read_exact(&mut l_buf)?;
-        reader.read_exact(&mut ct_buf)?;
-
-        // Convert to correct types
-        let length = u32::from_be_bytes(l_buf);
-        let chunk_type = ChunkType::try_from(ct_buf)?;
-
-        let data_terminator = length as usize + 8;
-
-        // Slice off the data and crc from the input bytes
-        let crc_bytes = bytes[data_terminator..data_terminator + 4].to_vec();
-        let data = bytes[8..data_terminator].to_vec();
-        
-        // Bit shift operation to convert from list of bytes to u32
-        let mut crc = 0;
-        for (i, b) in crc_bytes.iter().enumerate() {
-            let shift = (3 - i) * 8;
-            crc = crc + ((*b as u32) << shift);
+impl Chunk {
+    /// Parses the chunk at the start of `bytes`; anything after it is ignored.
+    ///
+    /// The length field comes from the input, so every offset derived from it
+    /// is checked rather than trusted: a malformed chunk is an error, never a
+    /// panic or a wrapped-around slice.
+    pub(crate) fn from_slice(bytes: &[u8], options: &ParseOptions) -> Result<Self> {
+        let chunk = Chunk::peek(bytes, options)?;
+
+        let crc_valid = chunk.crc_matches(options.crc);
+        if options.check_crc && !crc_valid {
+            Err(PngError::CrcMismatch)?;
         }
 
-        // Check CRC
-        let test_crc = calculate_crc(&chunk_type, &data);
-        if test_crc != crc {
-            Err("CRC mismatch")?;
+        Ok(Chunk {
+            length: chunk.length,
+            chunk_type: chunk.chunk_type,
+            data: chunk.data.to_vec(),
+            crc: chunk.crc,
+            crc_valid,
+            pending: None,
+        })
+    }
+
+    /// Reads the chunk at the start of `bytes` in place, with the same
+    /// checks on its length as `from_slice` but borrowing the data. The CRC
+    /// is left for the caller to check.
+    pub(crate) fn peek<'a>(bytes: &'a [u8], options: &ParseOptions) -> Result<ChunkRef<'a>> {
+        // The length and type come first, and the CRC needs 4 more bytes
+        let Some((header, rest)) = bytes.split_first_chunk::<8>() else {
+            Err("Invalid chunk length")?
+        };
+        let Some(available) = rest.len().checked_sub(4) else {
+            Err("Invalid chunk length")?
+        };
+
+        let length = u32::from_be_bytes(header[..4].try_into()?);
+        let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&header[4..])?)?;
+
+        if length > Chunk::MAX_LENGTH {
+            let message = format!(
+                "chunk length {} exceeds the PNG maximum of {}",
+                length,
+                Chunk::MAX_LENGTH
+            );
+            if !options.allow_oversize {
+                return Err(message.into());
+            }
+            log::warn!("{}; reading it anyway", message);
         }
 
-        Ok(Chunk {
+        // Reject lengths the input can't possibly satisfy before slicing
+        let data_len = usize::try_from(length)?;
+        if data_len > available {
+            Err(format!(
+                "chunk length {} exceeds the {} bytes available",
+                length, available
+            ))?;
+        }
+
+        // Within bounds now: data_len + 4 <= rest.len()
+        let (data, rest) = rest.split_at(data_len);
+        let crc = u32::from_be_bytes(rest[..4].try_into()?);
+
+        Ok(ChunkRef {
             length,
             chunk_type,
             data,
@@ -112,6 +307,34 @@ impl TryFrom<&Vec<u8>> for Chunk {
     }
 }
 
+/// A chunk read in place by `Chunk::peek`, its data still in the input.
+pub(crate) struct ChunkRef<'a> {
+    pub length: u32,
+    pub chunk_type: ChunkType,
+    pub data: &'a [u8],
+    pub crc: u32,
+}
+
+impl ChunkRef<'_> {
+    /// Whether the stored CRC matches the type and data under `crc`.
+    pub fn crc_matches(&self, crc: &Crc<u32>) -> bool {
+        checksum(crc, &self.chunk_type, self.data) == self.crc
+    }
+}
+
+/// Chunks are equal when they would serialize to the same bytes and agree on
+/// whether their CRC matched.
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Chunk) -> bool {
+        self.chunk_type == other.chunk_type
+            && self.data == other.data
+            && self.crc() == other.crc()
+            && self.crc_ok() == other.crc_ok()
+    }
+}
+
+impl Eq for Chunk {}
+
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let data_as_strings: Vec<String> = self.data.iter().map(|n| n.to_string()).collect();
@@ -119,15 +342,85 @@ impl fmt::Display for Chunk {
         write!(
             f,
             "{}{}{}{}",
-            self.length, self.chunk_type, joined_data, self.crc
+            self.length(),
+            self.chunk_type,
+            joined_data,
+            self.crc()
         )
     }
 }
 
-fn calculate_crc(chunk_type: &ChunkType, data: &Vec<u8>) -> u32 {
-    let mut crc_bytes = Vec::from(chunk_type.bytes());
-    crc_bytes.extend(data);
-    CRC.checksum(&crc_bytes.as_slice())
+/// The form a chunk takes when serialized: its type, Base64 data and CRC.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedChunk {
+    #[serde(rename = "type")]
+    chunk_type: ChunkType,
+    data: String,
+    crc: u32,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use base64::Engine;
+
+        SerializedChunk {
+            chunk_type: self.chunk_type.clone(),
+            data: base64::engine::general_purpose::STANDARD.encode(&self.data),
+            crc: self.crc(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializing checks the stored CRC against the decoded data.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use base64::Engine;
+        use serde::de::Error as _;
+
+        let serialized = SerializedChunk::deserialize(deserializer)?;
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&serialized.data)
+            .map_err(D::Error::custom)?;
+
+        let chunk = Chunk::new(serialized.chunk_type, data).map_err(D::Error::custom)?;
+        if chunk.crc != serialized.crc {
+            return Err(D::Error::custom("CRC mismatch"));
+        }
+        Ok(chunk)
+    }
+}
+
+/// Converts a data length to the chunk length field, refusing lengths the
+/// field can't represent rather than truncating them.
+fn checked_length(len: usize) -> Result<u32> {
+    match u32::try_from(len) {
+        Ok(length) if length <= Chunk::MAX_LENGTH => Ok(length),
+        _ => Err(format!(
+            "chunk data is {} bytes, more than the PNG maximum of {}",
+            len,
+            Chunk::MAX_LENGTH
+        ))?,
+    }
+}
+
+fn calculate_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    checksum(&PNG_CRC, chunk_type, data)
+}
+
+fn checksum(crc: &Crc<u32>, chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let mut digest = crc.digest();
+    digest.update(&chunk_type.bytes());
+    digest.update(data);
+    digest.finalize()
 }
 
 #[cfg(test)]
@@ -160,7 +453,7 @@ mod tests {
         let data = "This is where your secret message will be!"
             .as_bytes()
             .to_vec();
-        let chunk = Chunk::new(chunk_type, data);
+        let chunk = Chunk::new(chunk_type, data).unwrap();
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.crc(), 2882656334);
     }
@@ -177,6 +470,11 @@ mod tests {
         assert_eq!(chunk.chunk_type().to_string(), String::from("RuSt"));
     }
 
+    #[test]
+    fn test_type_str() {
+        assert_eq!(testing_chunk().type_str(), "RuSt");
+    }
+
     #[test]
     fn test_chunk_string() {
         let chunk = testing_chunk();
@@ -185,6 +483,211 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_serialized_len() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.serialized_len(), 54);
+        assert_eq!(chunk.serialized_len(), chunk.as_bytes().len());
+    }
+
+    #[test]
+    fn test_with_corrupt_crc() {
+        let bytes = testing_chunk().with_corrupt_crc().as_bytes();
+        assert!(Chunk::try_from(&bytes).is_err());
+
+        let chunk = Chunk::from_slice(&bytes, &ParseOptions::lenient()).unwrap();
+        assert_eq!(chunk.as_bytes(), bytes);
+        assert_ne!(chunk.crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_crc_ok_after_lenient_parse() {
+        let good = testing_chunk().as_bytes();
+        let bad = testing_chunk().with_corrupt_crc().as_bytes();
+        assert!(Chunk::from_slice(&good, &ParseOptions::lenient())
+            .unwrap()
+            .crc_ok());
+
+        let mut chunk = Chunk::from_slice(&bad, &ParseOptions::lenient()).unwrap();
+        assert!(!chunk.crc_ok());
+        chunk.recalculate_crc();
+        assert!(chunk.crc_ok());
+    }
+
+    #[test]
+    fn test_crc_with_high_bit_set() {
+        // 2882656334 is 0xABD1D84E, so the top bit is set
+        let chunk = testing_chunk();
+        assert_eq!(chunk.crc(), 0xABD1_D84E);
+        let parsed = Chunk::try_from(&chunk.as_bytes()).unwrap();
+        assert_eq!(parsed.crc(), 0xABD1_D84E);
+
+        for crc in [0x8000_0000, 0xFFFF_FFFF, 0x8000_0001, 0xFF00_00FF] {
+            let bytes = Chunk::new_with_crc(chunk.chunk_type().clone(), vec![1, 2], crc)
+                .unwrap()
+                .as_bytes();
+            assert_eq!(&bytes[10..], crc.to_be_bytes());
+
+            let parsed = Chunk::from_slice(&bytes, &ParseOptions::lenient()).unwrap();
+            assert_eq!(parsed.crc(), crc);
+        }
+    }
+
+    #[test]
+    fn test_new_with_crc() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new_with_crc(chunk_type, b"data".to_vec(), 0xDEAD_BEEF).unwrap();
+        assert_eq!(chunk.crc(), 0xDEAD_BEEF);
+        assert_eq!(chunk.length(), 4);
+
+        let err = Chunk::try_from(&chunk.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "CRC mismatch");
+        assert_eq!(err.downcast_ref(), Some(&PngError::CrcMismatch));
+    }
+
+    #[test]
+    fn test_crc_of() {
+        let chunk = testing_chunk();
+        assert_eq!(Chunk::crc_of(chunk.chunk_type(), chunk.data()), 2882656334);
+    }
+
+    #[test]
+    fn test_recalculate_crc() {
+        let mut chunk = testing_chunk();
+        chunk.data[0] = b't';
+        assert_eq!(chunk.crc(), 2882656334);
+
+        chunk.recalculate_crc();
+        assert_eq!(chunk.crc(), Chunk::crc_of(chunk.chunk_type(), chunk.data()));
+        assert!(Chunk::try_from(&chunk.as_bytes()).is_ok());
+
+        let mut corrupt = testing_chunk().with_corrupt_crc();
+        corrupt.recalculate_crc();
+        assert_eq!(corrupt.crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_checked_length() {
+        assert_eq!(checked_length(0).unwrap(), 0);
+        assert_eq!(checked_length(42).unwrap(), 42);
+        assert_eq!(
+            checked_length(Chunk::MAX_LENGTH as usize).unwrap(),
+            Chunk::MAX_LENGTH
+        );
+
+        let err = checked_length(Chunk::MAX_LENGTH as usize + 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk data is 2147483648 bytes, more than the PNG maximum of 2147483647"
+        );
+        assert!(checked_length(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_with_type() {
+        let new_type = ChunkType::from_str("myTx").unwrap();
+        let chunk = testing_chunk().with_type(new_type.clone());
+
+        assert_eq!(chunk.chunk_type(), &new_type);
+        assert_eq!(chunk.data(), testing_chunk().data());
+        assert_eq!(chunk.crc(), Chunk::crc_of(&new_type, chunk.data()));
+        assert!(Chunk::try_from(&chunk.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_set_data() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"Shorter message".to_vec()).unwrap();
+
+        assert_eq!(chunk.length(), 15);
+        assert_eq!(
+            chunk.crc(),
+            Chunk::crc_of(chunk.chunk_type(), b"Shorter message")
+        );
+        assert!(Chunk::try_from(&chunk.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_read_data() {
+        let chunk = testing_chunk();
+        let mut data = Vec::new();
+        chunk.read_data().read_to_end(&mut data).unwrap();
+        assert_eq!(data, chunk.data());
+    }
+
+    #[test]
+    fn test_data_mut() {
+        let mut chunk = testing_chunk().with_corrupt_crc();
+        chunk.data_mut().truncate(4);
+        chunk.data_mut().extend_from_slice(b" edited");
+
+        // Nothing changes until the edits are committed
+        assert_eq!(chunk, testing_chunk().with_corrupt_crc());
+        chunk.commit().unwrap();
+
+        assert_eq!(chunk.data(), b"This edited");
+        assert_eq!(chunk.length(), 11);
+        assert_eq!(
+            chunk.crc(),
+            Chunk::crc_of(chunk.chunk_type(), b"This edited")
+        );
+        assert!(chunk.crc_ok());
+        assert_eq!(chunk.serialized_len(), 23);
+
+        let parsed = Chunk::try_from(&chunk.as_bytes()).unwrap();
+        assert_eq!(parsed, chunk);
+        chunk.commit().unwrap();
+        assert_eq!(parsed, chunk);
+    }
+
+    #[test]
+    fn test_chunk_predicates() {
+        // RuSt: critical, private, safe to copy
+        let chunk = testing_chunk();
+        assert!(chunk.is_critical());
+        assert!(!chunk.is_ancillary());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    fn test_chunk_predicates_ancillary() {
+        let chunk = Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![]).unwrap();
+        assert!(!chunk.is_critical());
+        assert!(chunk.is_ancillary());
+        assert!(chunk.is_public());
+        assert!(!chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    fn test_chunk_eq() {
+        assert_eq!(testing_chunk(), testing_chunk());
+
+        let mut changed = testing_chunk();
+        changed.set_data(b"Something else".to_vec()).unwrap();
+        assert_ne!(testing_chunk(), changed);
+        assert_ne!(testing_chunk(), testing_chunk().with_corrupt_crc());
+    }
+
+    #[test]
+    fn test_alternate_crc_algorithm() {
+        static BZIP2: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_BZIP2);
+        let options = ParseOptions {
+            crc: &BZIP2,
+            ..Default::default()
+        };
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = b"This is where your secret message will be!".to_vec();
+        let chunk = Chunk::new_with_algorithm(chunk_type, data, &BZIP2).unwrap();
+        assert_ne!(chunk.crc(), 2882656334);
+
+        let bytes = chunk.as_bytes();
+        let parsed = Chunk::from_slice(&bytes, &options).unwrap();
+        assert_eq!(parsed, chunk);
+        assert!(Chunk::try_from(&bytes).is_err());
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -239,6 +742,169 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_empty_iend_chunk() {
+        let bytes: Vec<u8> = vec![0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+
+        let chunk = Chunk::try_from(&bytes).unwrap();
+        assert_eq!(chunk.length(), 0);
+        assert!(chunk.data().is_empty());
+        assert_eq!(chunk.crc(), 0xAE42_6082);
+        assert_eq!(chunk.as_bytes(), bytes);
+        assert_eq!(
+            chunk,
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap()
+        );
+
+        assert!(Chunk::try_from(&bytes[..11].to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_chunk_length_boundaries() {
+        let iend = Chunk::empty(ChunkType::from_ascii(b"IEND")).as_bytes();
+        for len in 0..12 {
+            let err = Chunk::from_slice(&iend[..len], &ParseOptions::default()).unwrap_err();
+            assert_eq!(err.to_string(), "Invalid chunk length");
+        }
+        assert!(Chunk::from_slice(&iend, &ParseOptions::default()).is_ok());
+
+        // Exactly 12 bytes claiming one byte of data leaves none for it
+        let mut bytes = iend.clone();
+        bytes[3] = 1;
+        let err = Chunk::try_from(&bytes).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 1 exceeds the 0 bytes available"
+        );
+
+        // The largest length the field holds must not wrap any offset
+        let options = ParseOptions {
+            allow_oversize: true,
+            ..Default::default()
+        };
+        bytes[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let err = Chunk::from_slice(&bytes, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("chunk length {} exceeds the 0 bytes available", u32::MAX)
+        );
+    }
+
+    #[test]
+    fn test_const_empty_chunk() {
+        const IEND: Chunk = Chunk::empty(ChunkType::from_ascii(b"IEND"));
+        assert_eq!(
+            IEND,
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap()
+        );
+        assert_eq!(IEND.crc(), 0xAE42_6082);
+    }
+
+    #[test]
+    fn test_chunk_length_exceeds_available_bytes() {
+        let chunk_data: Vec<u8> = 1000u32
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .chain(&[1, 2, 3])
+            .chain(&[0, 0, 0, 0])
+            .copied()
+            .collect();
+
+        let err = Chunk::try_from(&chunk_data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 1000 exceeds the 3 bytes available"
+        );
+    }
+
+    #[test]
+    fn test_allow_oversize() {
+        let chunk_data: Vec<u8> = 0x8000_0000u32
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .chain(&[0, 0, 0, 0])
+            .copied()
+            .collect();
+
+        let err = Chunk::from_slice(&chunk_data, &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 2147483648 exceeds the PNG maximum of 2147483647"
+        );
+
+        // A real oversize chunk needs 2 GiB of data; getting as far as the
+        // bounds check shows the limit itself was let through
+        let options = ParseOptions {
+            allow_oversize: true,
+            ..Default::default()
+        };
+        let err = Chunk::from_slice(&chunk_data, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 2147483648 exceeds the 0 bytes available"
+        );
+    }
+
+    #[test]
+    fn test_chunk_length_shorter_than_data() {
+        // The CRC is right for the data; only the length field is wrong
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let crc = Chunk::crc_of(&chunk_type, &[1, 2, 3]);
+        let chunk_data: Vec<u8> = 2u32
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .chain(&[1, 2, 3])
+            .chain(&crc.to_be_bytes())
+            .copied()
+            .collect();
+
+        let err = Chunk::try_from(&chunk_data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 2 doesn't match the 3 data bytes given"
+        );
+    }
+
+    #[test]
+    fn test_chunk_length_spec_maximum() {
+        let chunk_with_length = |length: u32| -> Vec<u8> {
+            length
+                .to_be_bytes()
+                .iter()
+                .chain("RuSt".as_bytes())
+                .chain(&[0, 0, 0, 0])
+                .copied()
+                .collect()
+        };
+
+        // 2^31 - 1 is allowed by the spec, so only the available-bytes check fails
+        let err = Chunk::try_from(&chunk_with_length(0x7FFF_FFFF)).unwrap_err();
+        assert!(err.to_string().contains("bytes available"));
+
+        let err = Chunk::try_from(&chunk_with_length(0x8000_0000)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk length 2147483648 exceeds the PNG maximum of 2147483647"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_json_round_trip() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert!(json.contains(r#""type":"RuSt""#));
+
+        let parsed: Chunk = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_bytes(), chunk.as_bytes());
+
+        let tampered = json.replace("2882656334", "2882656333");
+        assert!(serde_json::from_str::<Chunk>(&tampered).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -259,4 +925,28 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn test_chunk_round_trip(
+                chunk_type in "[A-Za-z]{4}",
+                // Failing cases shrink towards shorter data, down to empty
+                data in prop::collection::vec(any::<u8>(), 0..2048),
+            ) {
+                let chunk_type = ChunkType::from_str(&chunk_type).unwrap();
+                let chunk = Chunk::new(chunk_type, data.clone()).unwrap();
+                let bytes = chunk.as_bytes();
+
+                let parsed = Chunk::try_from(&bytes).unwrap();
+                prop_assert_eq!(parsed.length() as usize, data.len());
+                prop_assert_eq!(parsed.data(), &data[..]);
+                prop_assert_eq!(parsed.crc(), Chunk::crc_of(parsed.chunk_type(), &data));
+                prop_assert_eq!(parsed, chunk);
+            }
+        }
+    }
 }
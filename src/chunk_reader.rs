@@ -0,0 +1,221 @@
+use std::io::{ErrorKind, Read};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{PngError, Result};
+
+/// Reads chunks one at a time from a stream instead of loading the whole file.
+///
+/// Only the chunk currently being read is held in memory; its declared length
+/// is checked against `max_length` before the data is allocated.
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    max_length: u32,
+    done: bool,
+    position: u64,
+    progress: Option<Box<dyn FnMut(u64)>>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub const DEFAULT_MAX_LENGTH: u32 = 64 * 1024 * 1024;
+
+    /// Reader for a bare chunk stream, with no signature in front of it.
+    pub fn new(reader: R) -> Self {
+        ChunkReader {
+            reader,
+            max_length: Self::DEFAULT_MAX_LENGTH,
+            done: false,
+            position: 0,
+            progress: None,
+        }
+    }
+
+    /// Reader for a PNG stream; the signature is read and checked first.
+    pub fn from_png(mut reader: R) -> Result<Self> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if header != Png::STANDARD_HEADER {
+            Err(PngError::InvalidSignature)?;
+        }
+
+        let mut chunk_reader = Self::new(reader);
+        chunk_reader.position = header.len() as u64;
+        Ok(chunk_reader)
+    }
+
+    pub fn with_max_length(mut self, max_length: u32) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Calls `progress` after each chunk with the number of bytes read from
+    /// the stream so far, signature included.
+    pub fn with_progress(mut self, progress: impl FnMut(u64) + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut l_buf = [0; 4];
+        if !self.fill_or_eof(&mut l_buf)? {
+            return Ok(None);
+        }
+        let length = u32::from_be_bytes(l_buf);
+        if length > Chunk::MAX_LENGTH {
+            Err(format!(
+                "chunk length {} exceeds the PNG maximum of {}",
+                length,
+                Chunk::MAX_LENGTH
+            ))?;
+        }
+        if length > self.max_length {
+            Err(format!(
+                "chunk length {} exceeds the maximum of {}",
+                length, self.max_length
+            ))?;
+        }
+
+        let mut ct_buf = [0; 4];
+        self.reader.read_exact(&mut ct_buf)?;
+        let chunk_type = ChunkType::try_from(ct_buf)?;
+
+        let mut data = vec![0; length as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let mut crc_buf = [0; 4];
+        self.reader.read_exact(&mut crc_buf)?;
+
+        let chunk = Chunk::new(chunk_type, data)?;
+        if chunk.crc() != u32::from_be_bytes(crc_buf) {
+            Err(PngError::CrcMismatch)?;
+        }
+
+        self.position += chunk.serialized_len() as u64;
+        if let Some(progress) = &mut self.progress {
+            progress(self.position);
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Fills `buf` completely, or returns `false` if the stream ended cleanly
+    /// before the first byte.
+    fn fill_or_eof(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => Err("unexpected end of chunk stream")?,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => Err(e)?,
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_chunk().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"first".to_vec()).unwrap(),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), vec![]).unwrap(),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"last".to_vec()).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_reads_every_chunk() {
+        let bytes = testing_png().as_bytes();
+        let reader = ChunkReader::from_png(Cursor::new(bytes)).unwrap();
+
+        let chunks: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].data(), b"first");
+        assert_eq!(chunks[1].length(), 0);
+        assert_eq!(&chunks[2].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_progress_counts_bytes_read() {
+        let bytes = testing_png().as_bytes();
+        let total = bytes.len() as u64;
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&seen);
+        let reader = ChunkReader::from_png(Cursor::new(bytes))
+            .unwrap()
+            .with_progress(move |n| log.borrow_mut().push(n));
+        assert_eq!(reader.count(), 3);
+
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 3);
+        assert!(seen.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(seen.last(), Some(&total));
+    }
+
+    #[test]
+    fn test_rejects_length_over_maximum() {
+        let bytes = testing_png().as_bytes();
+        let mut reader = ChunkReader::from_png(Cursor::new(bytes))
+            .unwrap()
+            .with_max_length(4);
+
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let bytes = testing_png().as_bytes();
+        let reader = ChunkReader::from_png(Cursor::new(&bytes[..bytes.len() - 2])).unwrap();
+
+        let results: Vec<Result<Chunk>> = reader.collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let bytes = Chunk::new(ChunkType::from_str("FrSt").unwrap(), vec![])
+            .unwrap()
+            .as_bytes();
+        let err = ChunkReader::from_png(Cursor::new(bytes)).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&PngError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let mut reader = ChunkReader::from_png(Cursor::new(bytes)).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PngError::CrcMismatch));
+    }
+}
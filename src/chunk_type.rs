@@ -1,45 +1,219 @@
-use std::{convert::TryFrom, str::FromStr, fmt };
 use crate::{Error, Result};
-#[derive(Debug)]
+use std::{convert::TryFrom, fmt, str::FromStr};
+/// Chunk types defined by the PNG specification, with a short description.
+pub const STANDARD_TYPES: &[(ChunkType, &str)] = &[
+    (ChunkType::from_ascii(b"IHDR"), "Image header"),
+    (ChunkType::from_ascii(b"PLTE"), "Palette"),
+    (ChunkType::from_ascii(b"IDAT"), "Image data"),
+    (ChunkType::from_ascii(b"IEND"), "Image trailer"),
+    (
+        ChunkType::from_ascii(b"cHRM"),
+        "Primary chromaticities and white point",
+    ),
+    (ChunkType::from_ascii(b"gAMA"), "Image gamma"),
+    (ChunkType::from_ascii(b"iCCP"), "Embedded ICC profile"),
+    (ChunkType::from_ascii(b"sBIT"), "Significant bits"),
+    (ChunkType::from_ascii(b"sRGB"), "Standard RGB colour space"),
+    (ChunkType::from_ascii(b"bKGD"), "Background colour"),
+    (ChunkType::from_ascii(b"hIST"), "Image histogram"),
+    (ChunkType::from_ascii(b"tRNS"), "Transparency"),
+    (ChunkType::from_ascii(b"pHYs"), "Physical pixel dimensions"),
+    (ChunkType::from_ascii(b"sPLT"), "Suggested palette"),
+    (
+        ChunkType::from_ascii(b"tIME"),
+        "Image last-modification time",
+    ),
+    (ChunkType::from_ascii(b"iTXt"), "International textual data"),
+    (ChunkType::from_ascii(b"tEXt"), "Textual data"),
+    (ChunkType::from_ascii(b"zTXt"), "Compressed textual data"),
+];
+
+/// Chunk types added by the APNG extension, with the role each plays.
+pub const APNG_TYPES: &[(ChunkType, &str)] = &[
+    (ChunkType::from_ascii(b"acTL"), "APNG control"),
+    (ChunkType::from_ascii(b"fcTL"), "APNG control"),
+    (ChunkType::from_ascii(b"fdAT"), "APNG frame data"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ChunkType {
     ancillary: u8,
     private: u8,
     reserved: u8,
-    safe_to_copy: u8
+    safe_to_copy: u8,
 }
 
 impl ChunkType {
-    pub fn bytes(&self) -> [u8; 4] {
-        [self.ancillary, self.private, self.reserved, self.safe_to_copy]
+    /// Builds a chunk type from four letters and the properties it should
+    /// carry, setting the case of each letter to match.
+    ///
+    /// The case of letters passed in is ignored. Each property maps onto one
+    /// letter's case (bit 5 of the byte):
+    ///
+    /// - 1st letter: uppercase if `critical`, lowercase if ancillary
+    /// - 2nd letter: uppercase if `public`, lowercase if private
+    /// - 3rd letter: uppercase if `reserved_valid`, which it must be in a valid type
+    /// - 4th letter: lowercase if `safe_to_copy`, uppercase otherwise
+    pub fn new(
+        letters: [u8; 4],
+        critical: bool,
+        public: bool,
+        reserved_valid: bool,
+        safe_to_copy: bool,
+    ) -> Result<Self> {
+        if !letters.iter().all(|b| b.is_ascii_alphabetic()) {
+            Err("chunk type must be four ASCII letters")?;
+        }
+
+        let with_case = |byte: u8, uppercase: bool| {
+            if uppercase {
+                byte.to_ascii_uppercase()
+            } else {
+                byte.to_ascii_lowercase()
+            }
+        };
+
+        Self::try_from([
+            with_case(letters[0], critical),
+            with_case(letters[1], public),
+            with_case(letters[2], reserved_valid),
+            with_case(letters[3], !safe_to_copy),
+        ])
+    }
+
+    /// Builds a chunk type from bytes known at compile time, such as `b"IHDR"`,
+    /// without the `Result` of `TryFrom`.
+    ///
+    /// Panics if any byte is not an ASCII letter, which in a `const` is a
+    /// compile error; use `TryFrom` for untrusted input.
+    pub const fn from_ascii(bytes: &[u8; 4]) -> ChunkType {
+        let mut i = 0;
+        while i < 4 {
+            assert!(
+                bytes[i].is_ascii_alphabetic(),
+                "chunk type bytes must be ASCII letters"
+            );
+            i += 1;
+        }
+
+        ChunkType {
+            ancillary: bytes[0],
+            private: bytes[1],
+            reserved: bytes[2],
+            safe_to_copy: bytes[3],
+        }
+    }
+
+    pub const fn bytes(&self) -> [u8; 4] {
+        [
+            self.ancillary,
+            self.private,
+            self.reserved,
+            self.safe_to_copy,
+        ]
+    }
+
+    /// The four letters of the type, in order.
+    pub fn chars(&self) -> impl Iterator<Item = char> {
+        self.bytes().into_iter().map(char::from)
     }
 
-    pub fn is_valid(&self) -> bool {
+    /// The first byte, whose case says whether the chunk is critical.
+    pub const fn ancillary_byte(&self) -> u8 {
+        self.ancillary
+    }
+
+    /// The second byte, whose case says whether the type is public.
+    pub const fn private_byte(&self) -> u8 {
+        self.private
+    }
+
+    /// The third byte, which must be uppercase in a valid type.
+    pub const fn reserved_byte(&self) -> u8 {
+        self.reserved
+    }
+
+    /// The fourth byte, whose case says whether the chunk is safe to copy.
+    pub const fn safe_to_copy_byte(&self) -> u8 {
+        self.safe_to_copy
+    }
+
+    pub const fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
 
-    pub fn is_critical(&self) -> bool {
+    pub const fn is_critical(&self) -> bool {
         Self::check_property_bit(&self.ancillary)
     }
 
-    pub fn is_public(&self) -> bool {
+    pub const fn is_public(&self) -> bool {
         Self::check_property_bit(&self.private)
     }
 
-    pub fn is_reserved_bit_valid(&self) -> bool {
+    pub const fn is_reserved_bit_valid(&self) -> bool {
         Self::check_property_bit(&self.reserved)
     }
 
-    pub fn is_safe_to_copy(&self) -> bool {
+    pub const fn is_safe_to_copy(&self) -> bool {
         !Self::check_property_bit(&self.safe_to_copy)
     }
 
-    fn check_property_bit(byte: &u8) -> bool {
-        byte & 32 == 0
+    /// Whether both types have the same letters, ignoring the case that
+    /// carries their property bits; `==` compares the bytes exactly.
+    pub fn eq_ignore_case(&self, other: &ChunkType) -> bool {
+        self.bytes().eq_ignore_ascii_case(&other.bytes())
+    }
+
+    /// The type's letters followed by the property each letter's case encodes,
+    /// e.g. `"RuSt (critical, private, reserved-valid, safe-to-copy)"`.
+    pub fn describe(&self) -> String {
+        let pick = |set: bool, yes: &'static str, no: &'static str| if set { yes } else { no };
+        let properties = [
+            pick(self.is_critical(), "critical", "ancillary"),
+            pick(self.is_public(), "public", "private"),
+            pick(
+                self.is_reserved_bit_valid(),
+                "reserved-valid",
+                "reserved-invalid",
+            ),
+            pick(self.is_safe_to_copy(), "safe-to-copy", "unsafe-to-copy"),
+        ];
+        format!("{} ({})", self, properties.join(", "))
+    }
+
+    /// The description of this type from the PNG specification, if it is a
+    /// standard chunk type.
+    pub fn standard_description(&self) -> Option<&'static str> {
+        STANDARD_TYPES
+            .iter()
+            .find(|(chunk_type, _)| chunk_type == self)
+            .map(|(_, description)| *description)
+    }
+
+    /// Whether this is an APNG control or frame data chunk type, and which.
+    pub fn apng_role(&self) -> Option<&'static str> {
+        APNG_TYPES
+            .iter()
+            .find(|(chunk_type, _)| chunk_type == self)
+            .map(|(_, role)| *role)
+    }
+
+    const fn check_property_bit(byte: &u8) -> bool {
+        *byte & 32 == 0
     }
 }
 
+/// Both constructors accept exactly four ASCII letters and nothing else; the
+/// property bits are only checked by `is_valid`.
 impl TryFrom<[u8; 4]> for ChunkType {
     fn try_from(bytes: [u8; 4]) -> Result<Self> {
+        if !bytes.iter().all(u8::is_ascii_alphabetic) {
+            Err(format!(
+                "chunk type bytes {:?} are not all ASCII letters",
+                bytes
+            ))?;
+        }
+
         Ok(ChunkType {
             ancillary: bytes[0],
             private: bytes[1],
@@ -50,34 +224,70 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
 }
 
-impl PartialEq for ChunkType {
-    fn eq(&self, other: &Self) -> bool {
-        self.bytes() == other.bytes()
-    }
-}
-
 impl FromStr for ChunkType {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
-        if s.chars().all(|c| c.is_alphabetic()) {
-            let mut a: [u8; 4] = Default::default();
-            a.copy_from_slice(&s.as_bytes()[0..4]);
-            Ok(Self::try_from(a)?)
-        } else {
-            Err("Could not convert to string")?
-        }        
+        let bytes: [u8; 4] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| format!("chunk type '{}' must be exactly four letters", s))?;
+        Self::try_from(bytes)
+            .map_err(|_| format!("chunk type '{}' must be ASCII letters only", s).into())
     }
 }
 
-impl fmt::Display for ChunkType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let chunk = [self.ancillary, self.private, self.reserved, self.safe_to_copy];
-        write!(f, "{}", std::str::from_utf8(&chunk).unwrap())
+/// Compares the exact bytes, case included, so `"rust"` is not `RuSt`.
+impl PartialEq<str> for ChunkType {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for ChunkType {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Serialized as its four-letter string, e.g. `"RuSt"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
 
+impl From<ChunkType> for [u8; 4] {
+    fn from(chunk_type: ChunkType) -> Self {
+        chunk_type.bytes()
+    }
+}
+
+/// The four letters of the type, e.g. `"RuSt"`.
+impl From<ChunkType> for String {
+    fn from(chunk_type: ChunkType) -> Self {
+        chunk_type.to_string()
+    }
+}
 
+impl fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.chars().try_for_each(|c| write!(f, "{}", c))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -92,6 +302,21 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_invalid_bytes() {
+        assert!(ChunkType::try_from([49, 50, 51, 52]).is_err());
+        assert!(ChunkType::try_from([82, 117, 0, 116]).is_err());
+        assert!(ChunkType::try_from([82, 117, 0xC3, 116]).is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_str_wrong_length() {
+        assert!(ChunkType::from_str("Rus").is_err());
+        assert!(ChunkType::from_str("RuStY").is_err());
+        assert!(ChunkType::from_str("").is_err());
+        assert!(ChunkType::from_str("Ruñ").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
@@ -168,6 +393,142 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_new() {
+        let chunk = ChunkType::new(*b"rust", false, false, true, true).unwrap();
+        assert_eq!(&chunk.to_string(), "ruSt");
+        assert!(chunk.is_valid());
+
+        let chunk = ChunkType::new(*b"RUST", true, true, true, false).unwrap();
+        assert_eq!(&chunk.to_string(), "RUST");
+    }
+
+    #[test]
+    pub fn test_chunk_type_new_flags() {
+        let base = ChunkType::new(*b"rust", false, false, false, false).unwrap();
+        assert_eq!(&base.to_string(), "rusT");
+
+        let critical = ChunkType::new(*b"rust", true, false, false, false).unwrap();
+        assert!(critical.is_critical());
+        assert_eq!(critical.bytes()[0], base.bytes()[0] ^ 32);
+
+        let public = ChunkType::new(*b"rust", false, true, false, false).unwrap();
+        assert!(public.is_public());
+        assert_eq!(public.bytes()[1], base.bytes()[1] ^ 32);
+
+        let reserved = ChunkType::new(*b"rust", false, false, true, false).unwrap();
+        assert!(reserved.is_reserved_bit_valid());
+        assert_eq!(reserved.bytes()[2], base.bytes()[2] ^ 32);
+
+        let safe = ChunkType::new(*b"rust", false, false, false, true).unwrap();
+        assert!(safe.is_safe_to_copy());
+        assert_eq!(safe.bytes()[3], base.bytes()[3] ^ 32);
+    }
+
+    #[test]
+    pub fn test_chunk_type_new_rejects_non_letters() {
+        assert!(ChunkType::new(*b"ru5t", false, false, true, true).is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_ascii() {
+        const IHDR: ChunkType = ChunkType::from_ascii(b"IHDR");
+        assert_eq!(IHDR, ChunkType::from_str("IHDR").unwrap());
+        assert!(IHDR.is_critical());
+    }
+
+    #[test]
+    pub fn test_standard_types_match_from_str() {
+        const IEND_CRITICAL: bool = ChunkType::from_ascii(b"IEND").is_critical();
+        const { assert!(IEND_CRITICAL) };
+        for (chunk_type, _) in STANDARD_TYPES.iter().chain(APNG_TYPES) {
+            assert_eq!(
+                chunk_type,
+                &ChunkType::from_str(&chunk_type.to_string()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk type bytes must be ASCII letters")]
+    pub fn test_chunk_type_from_ascii_rejects_non_letters() {
+        ChunkType::from_ascii(b"ru5t");
+    }
+
+    #[test]
+    pub fn test_chunk_type_eq_str() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert!(chunk_type == "RuSt");
+        assert!(chunk_type != "rust");
+        assert!(chunk_type != "RuS");
+        assert!(chunk_type != "RuStY");
+        assert!(&chunk_type == "RuSt");
+    }
+
+    #[test]
+    pub fn test_chars_and_bytes() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(chunk_type.chars().collect::<Vec<_>>(), ['R', 'u', 'S', 't']);
+        assert_eq!(chunk_type.ancillary_byte(), b'R');
+        assert_eq!(chunk_type.private_byte(), b'u');
+        assert_eq!(chunk_type.reserved_byte(), b'S');
+        assert_eq!(chunk_type.safe_to_copy_byte(), b't');
+    }
+
+    #[test]
+    pub fn test_eq_ignore_case() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let other = ChunkType::from_str("rust").unwrap();
+        assert_ne!(chunk, other);
+        assert!(chunk.eq_ignore_case(&other));
+        assert!(!chunk.eq_ignore_case(&ChunkType::from_str("RuSk").unwrap()));
+    }
+
+    #[test]
+    pub fn test_describe() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(
+            chunk.describe(),
+            "RuSt (critical, private, reserved-valid, safe-to-copy)"
+        );
+
+        let chunk = ChunkType::from_str("tEXT").unwrap();
+        assert_eq!(
+            chunk.describe(),
+            "tEXT (ancillary, public, reserved-valid, unsafe-to-copy)"
+        );
+    }
+
+    #[test]
+    pub fn test_standard_description() {
+        let chunk = ChunkType::from_str("tEXt").unwrap();
+        assert_eq!(chunk.standard_description(), Some("Textual data"));
+
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(chunk.standard_description(), None);
+    }
+
+    #[test]
+    pub fn test_apng_role() {
+        let chunk = ChunkType::from_str("fcTL").unwrap();
+        assert_eq!(chunk.apng_role(), Some("APNG control"));
+
+        let chunk = ChunkType::from_str("fdAT").unwrap();
+        assert_eq!(chunk.apng_role(), Some("APNG frame data"));
+
+        let chunk = ChunkType::from_str("IDAT").unwrap();
+        assert_eq!(chunk.apng_role(), None);
+    }
+
+    #[test]
+    pub fn test_chunk_type_into() {
+        let bytes: [u8; 4] = ChunkType::from_str("RuSt").unwrap().into();
+        assert_eq!(bytes, *b"RuSt");
+
+        let string: String = ChunkType::from_str("RuSt").unwrap().into();
+        assert_eq!(string, "RuSt");
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
@@ -175,4 +536,4 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,4407 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use regex::Regex;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::args::{
+    ArchiveArgs, BackupArgs, ChunkTypesArgs, ColorChoice, Commands, CountArgs, DecodeArgs,
+    DecodeFormat, DiffArgs, EncodeArgs, EntropyArgs, ExtractArgs, FixArgs, HashAlgorithm, HashArgs,
+    InfoArgs, InputFormat, InsertArgs, ListArgs, MergeArgs, NormalizeArgs, PrintArgs,
+    QuickstartArgs, RelabelArgs, RemoveArgs, ScanArgs, StripArgs, TextEncoding, TypeinfoArgs,
+    UnarchiveArgs, VerifyArgs,
+};
+use crate::chunk::{Chunk, PNG_CRC};
+use crate::chunk_reader::ChunkReader;
+use crate::chunk_type::{ChunkType, STANDARD_TYPES};
+use crate::integrity;
+use crate::logging::Logger;
+use crate::pack;
+use crate::png::{self, ParseOptions, Png, Time};
+use crate::rng::Rng;
+use crate::split;
+use crate::text;
+use crate::{Error, Result};
+
+pub fn run(command: &Commands, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    match command {
+        Commands::Encode(args) => encode(args, out, logger),
+        Commands::Decode(args) => decode(args, out, logger),
+        Commands::Remove(args) => remove(args, out, logger),
+        Commands::Insert(args) => insert(args, out, logger),
+        Commands::Print(args) => print(args, out, logger),
+        Commands::Diff(args) => diff(args, out, logger),
+        Commands::Strip(args) => strip(args, out, logger),
+        Commands::Merge(args) => merge(args, out, logger),
+        Commands::Info(args) => info(args, out, logger),
+        Commands::List(args) => list(args, out, logger),
+        Commands::Typeinfo(args) => typeinfo(args, out, logger),
+        Commands::ChunkTypes(args) => chunk_types(args, out, logger),
+        Commands::Quickstart(args) => quickstart(args, out, logger),
+        Commands::Count(args) => count(args, out, logger),
+        Commands::Extract(args) => extract(args, out, logger),
+        Commands::Verify(args) => verify(args, out, logger),
+        Commands::Fix(args) => fix(args, out, logger),
+        Commands::Normalize(args) => normalize(args, out, logger),
+        Commands::Relabel(args) => relabel(args, out, logger),
+        Commands::Hash(args) => hash(args, out, logger),
+        Commands::Archive(args) => archive(args, out, logger),
+        Commands::Unarchive(args) => unarchive(args, out, logger),
+        Commands::Scan(args) => scan(args, out, logger),
+        Commands::Entropy(args) => entropy(args, out, logger),
+    }
+}
+
+/// The file name that stands for standard input, or standard output when
+/// written to.
+const STDIO: &str = "-";
+
+pub fn encode(args: &EncodeArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let output = encode_output(args)?;
+    let piped = output == STDIO && !args.dry_run && !args.plan;
+    let logger = &logger.with_info_on_stderr(piped);
+    let signature = args.signature.as_deref().map(parse_signature).transpose()?;
+    let mut png = read_png(&args.filepath, logger)?;
+    // What --plan lists, recorded as each step is applied to the PNG
+    let mut steps = vec![format!("read {}", args.filepath)];
+
+    let chunks = match (&args.spec, &args.chunk_type) {
+        (Some(spec), _) => spec_chunks(spec)?,
+        (None, _) if args.auto_type => {
+            let chunk_type = auto_chunk_type(&png, args.seed);
+            if !args.plan {
+                logger.output(out, format_args!("chunk type: {}", chunk_type))?;
+            }
+            message_chunks(chunk_type, args)?
+        }
+        (None, Some(chunk_type)) => message_chunks(ChunkType::from_str(chunk_type)?, args)?,
+        (None, None) => Err("a chunk type is required")?,
+    };
+
+    for chunk in &chunks {
+        check_chunk_type(chunk.chunk_type(), args.strict, logger)?;
+    }
+    let chunks = if args.decoys > 0 {
+        with_decoys(chunks, args.decoys, args.seed)?
+    } else {
+        chunks
+    };
+
+    // Running the same encode twice shouldn't store the message twice,
+    // though appending is meant to repeat it
+    let present = |chunk: &Chunk| png.chunks().contains(chunk);
+    let duplicate = !args.allow_duplicate && !args.append_to_existing;
+    if duplicate && !chunks.is_empty() && chunks.iter().all(present) {
+        if let Some(anchor) = &args.after {
+            png.index_after_type(anchor)?;
+        }
+        if args.plan {
+            steps.push("leave the message, which is already there".to_string());
+            if output != args.filepath || output == STDIO {
+                steps.push(format!("write to {}", output));
+            }
+            return print_plan(&steps, out);
+        }
+        return encode_unchanged(&png, args, out, logger);
+    }
+
+    if !args.replace && !args.append_to_existing {
+        warn_duplicate_types(&chunks, &png, logger);
+    }
+
+    let original_size = png.total_size();
+    for chunk in chunks {
+        let chunk_type = chunk.type_str();
+        let exists = png.chunk_by_type(&chunk_type).is_some();
+        let replace = args.replace && exists;
+        let append = args.append_to_existing && exists;
+        let action = match (replace, append) {
+            (true, _) => "replace",
+            (_, true) => "append to",
+            _ => "add",
+        };
+        let mut step = format!("{} {} chunk ({} bytes)", action, chunk_type, chunk.length());
+        if let (false, false, Some(anchor)) = (replace, append, &args.after) {
+            step.push_str(&format!(" after {}", anchor));
+        }
+        if args.dry_run && !args.plan {
+            writeln!(out, "would {}", step)?;
+        }
+        steps.push(step);
+
+        if replace {
+            png.replace_chunk(&chunk_type, chunk)?;
+        } else if append {
+            png.append_to_chunk(&chunk_type, chunk.data())?;
+        } else if let Some(anchor) = &args.after {
+            png.insert_after_type(anchor, chunk)?;
+        } else {
+            png.insert_before_iend(chunk);
+        }
+    }
+    if args.drop_trailing {
+        let dropped = png.take_trailing_bytes().len();
+        steps.push(format!("drop {} trailing bytes", dropped));
+    }
+    if let Some(signature) = signature {
+        png.set_header(signature);
+        let hex: String = signature.iter().map(|b| format!("{:02x}", b)).collect();
+        steps.push(format!("write signature {}", hex));
+    }
+    if args.plan {
+        steps.push(format!("write to {}", output));
+        return print_plan(&steps, out);
+    }
+
+    let new_size = png.total_size();
+    if new_size >= original_size {
+        logger.info(
+            out,
+            format_args!("file will grow by {} bytes", new_size - original_size),
+        )?;
+    } else {
+        logger.info(
+            out,
+            format_args!("file will shrink by {} bytes", original_size - new_size),
+        )?;
+    }
+
+    if args.output.is_none() && !args.dry_run {
+        if let Some(dir) = &args.output_dir {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        }
+    }
+    if !args.dry_run {
+        back_up(&args.filepath, &output, &args.backup, logger)?;
+    }
+    write_png(&png, &output, args.dry_run, out, logger)
+}
+
+/// Prints the numbered steps of `encode --plan`.
+fn print_plan(steps: &[String], out: &mut impl Write) -> Result<()> {
+    for (i, step) in steps.iter().enumerate() {
+        writeln!(out, "{}. {}", i + 1, step)?;
+    }
+    Ok(())
+}
+
+/// Finishes an encode whose message is already stored, failing on a bad
+/// anchor or backup just as storing it would. Nothing is rewritten in place,
+/// but any other output still gets the PNG.
+fn encode_unchanged(
+    png: &Png,
+    args: &EncodeArgs,
+    out: &mut impl Write,
+    logger: &Logger,
+) -> Result<()> {
+    let output = encode_output(args)?;
+    if !args.dry_run {
+        back_up(&args.filepath, &output, &args.backup, logger)?;
+    }
+    logger.info(out, format_args!("message already present, nothing to do"))?;
+
+    if output == args.filepath && output != STDIO {
+        return Ok(());
+    }
+    if args.output.is_none() && !args.dry_run {
+        if let Some(dir) = &args.output_dir {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        }
+    }
+    write_png(png, &output, args.dry_run, out, logger)
+}
+
+/// Surrounds the single message chunk in `chunks` with `decoys` chunks of
+/// the same type and length holding random bytes, so the real one can't be
+/// told apart by its size or position.
+fn with_decoys(chunks: Vec<Chunk>, decoys: usize, seed: Option<u64>) -> Result<Vec<Chunk>> {
+    let [message]: [Chunk; 1] = chunks
+        .try_into()
+        .map_err(|_| "--decoys needs exactly one message chunk")?;
+    let mut rng = seed.map(Rng::new).unwrap_or_else(Rng::from_time);
+
+    let mut chunks = Vec::with_capacity(decoys + 1);
+    for _ in 0..decoys {
+        let data = (0..message.length())
+            .map(|_| rng.next_u64() as u8)
+            .collect();
+        chunks.push(Chunk::new(message.chunk_type().clone(), data)?);
+    }
+    chunks.insert(rng.below(decoys as u64 + 1) as usize, message);
+    Ok(chunks)
+}
+
+/// Where encode writes: the explicit output, a `.stego.png` name derived from
+/// the input inside `--output-dir`, or the input file itself.
+fn encode_output(args: &EncodeArgs) -> Result<String> {
+    match (&args.output, &args.output_dir) {
+        (Some(output), _) => Ok(output.clone()),
+        (None, Some(dir)) => {
+            let stem = Path::new(&args.filepath).file_stem().ok_or_else(|| {
+                format!("{}: no file name to derive an output from", args.filepath)
+            })?;
+            let name = format!("{}.stego.png", stem.to_string_lossy());
+            Ok(Path::new(dir).join(name).to_string_lossy().into_owned())
+        }
+        (None, None) => Ok(args.filepath.clone()),
+    }
+}
+
+/// Warns once for each type in `chunks` that the file already has a chunk
+/// of, since `decode` only ever reads the first.
+fn warn_duplicate_types(chunks: &[Chunk], png: &Png, logger: &Logger) {
+    let mut warned: Vec<&ChunkType> = Vec::new();
+    for chunk_type in chunks.iter().map(Chunk::chunk_type) {
+        let exists = png.chunks().iter().any(|c| c.chunk_type() == chunk_type);
+        if exists && !warned.contains(&chunk_type) {
+            logger.warn(format_args!(
+                "the file already has a {} chunk; this adds a duplicate and decode will \
+                 still read the first one (use --replace to overwrite it)",
+                chunk_type
+            ));
+            warned.push(chunk_type);
+        }
+    }
+}
+
+pub fn decode(args: &DecodeArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png_at(
+        &args.filepath,
+        args.offset,
+        args.input.format,
+        args.oversize.allow_oversize,
+        logger,
+    )?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let chunk = png
+        .chunks()
+        .iter()
+        .find(|c| {
+            c.chunk_type() == &chunk_type
+                || (args.ignore_case && c.chunk_type().eq_ignore_case(&chunk_type))
+        })
+        .ok_or_else(|| {
+            let suggestion = closest_chunk_type(&png, &args.chunk_type)
+                .map(|t| format!("; did you mean '{}'?", t))
+                .unwrap_or_default();
+            format!("chunk type '{}' not found{}", args.chunk_type, suggestion)
+        })?;
+
+    let transformed = args.join || args.decompress || args.verified || args.unpack.is_some();
+    if !transformed
+        && args.output_format() == DecodeFormat::Text
+        && args.encoding == TextEncoding::Utf8
+    {
+        // Checked and written as it is read, so a large message isn't copied
+        text::copy_utf8(chunk.read_data(), out)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    let data = if args.join {
+        split::join_parts(png.chunks_by_type(&chunk.type_str()))?
+    } else {
+        chunk.data().to_vec()
+    };
+    let data = if args.decompress {
+        text::decompress_text(&data)?.1
+    } else {
+        data
+    };
+    let data = if args.verified {
+        match integrity::unseal(&data) {
+            Ok(payload) => payload.to_vec(),
+            Err(e) if args.join || args.decompress => Err(e)?,
+            // encode --decoys hides the message among chunks of the same
+            // type that fail the check, so fall back to the first that passes
+            Err(e) => png
+                .chunks_by_type(&chunk.type_str())
+                .find_map(|c| integrity::unseal(c.data()).ok())
+                .map(<[u8]>::to_vec)
+                .ok_or(e)?,
+        }
+    } else {
+        data
+    };
+    let data = match &args.unpack {
+        Some(name) => pack::unpack_entries(&data)?
+            .into_iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("no packed message named '{}'", name))?,
+        None => data,
+    };
+
+    match args.output_format() {
+        DecodeFormat::Text => {
+            let text = match args.encoding {
+                TextEncoding::Utf8 => String::from_utf8(data).map_err(|_| "not valid utf8")?,
+                TextEncoding::Latin1 => text::latin1_to_string(&data),
+            };
+            writeln!(out, "{}", text)?
+        }
+        DecodeFormat::Hex => {
+            let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+            writeln!(out, "{}", hex)?
+        }
+        DecodeFormat::Base64 => writeln!(out, "{}", STANDARD.encode(data))?,
+        DecodeFormat::Raw => out.write_all(&data)?,
+    }
+
+    Ok(())
+}
+
+/// The chunk type in `png` nearest to `wanted` by edit distance, if any is
+/// close enough to be a likely typo; ties go to the one that comes first.
+fn closest_chunk_type(png: &Png, wanted: &str) -> Option<String> {
+    png.chunks()
+        .iter()
+        .map(|chunk| chunk.type_str())
+        .map(|name| (levenshtein(wanted.as_bytes(), name.as_bytes()), name))
+        .filter(|&(distance, _)| distance <= 2)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, name)| name)
+}
+
+/// The number of single-byte insertions, deletions and substitutions that
+/// turn `a` into `b`. Case counts, as it does in chunk types.
+fn levenshtein(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, &byte_a) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &byte_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(byte_a != byte_b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub fn remove(args: &RemoveArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let logger = &piping_logger(logger, &args.filepath, args.dry_run);
+    let chunk_types = args
+        .chunk_types
+        .iter()
+        .map(|t| ChunkType::from_str(t))
+        .collect::<Result<Vec<_>>>()?;
+    let mut png = read_png(&args.filepath, logger)?;
+
+    let mut total = 0;
+    for chunk_type in &chunk_types {
+        let mut matching = vec![chunk_type.clone()];
+        if args.ignore_case {
+            for chunk in png.chunks() {
+                let other = chunk.chunk_type();
+                if other.eq_ignore_case(chunk_type) && !matching.contains(other) {
+                    matching.push(other.clone());
+                }
+            }
+        }
+        for chunk_type in &matching {
+            let removed = png.remove_chunks_of_type(chunk_type);
+            warn_orphaned_frames(&removed, &png, logger);
+            if chunk_type == &matching[0] || !removed.is_empty() {
+                logger.info(
+                    out,
+                    format_args!("removed {} {} chunks", removed.len(), chunk_type),
+                )?;
+            }
+            total += removed.len();
+        }
+    }
+    if total == 0 {
+        Err("no chunks of the given types found")?;
+    }
+    if args.drop_trailing {
+        png.take_trailing_bytes();
+    }
+    if !args.dry_run {
+        back_up(&args.filepath, &args.filepath, &args.backup, logger)?;
+    }
+    write_png(&png, &args.filepath, args.dry_run, out, logger)
+}
+
+pub fn insert(args: &InsertArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let logger = &piping_logger(logger, &args.filepath, false);
+    let mut png = read_png(&args.filepath, logger)?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    check_chunk_type(&chunk_type, false, logger)?;
+
+    let chunk = Chunk::new(chunk_type, args.message.as_bytes().to_vec())?;
+    png.insert_chunk_at(args.index, chunk)?;
+    back_up(&args.filepath, &args.filepath, &args.backup, logger)?;
+    write_png(&png, &args.filepath, false, out, logger)
+}
+
+pub fn print(args: &PrintArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let filter = ChunkFilter::new(
+        args.only_critical,
+        args.only_ancillary,
+        args.type_filter.as_deref(),
+    )?;
+    for_each_file(&args.filepaths, out, |path, out| {
+        print_file(args, &filter, path, out, logger)
+    })
+}
+
+fn print_file(
+    args: &PrintArgs,
+    filter: &ChunkFilter,
+    path: &str,
+    out: &mut impl Write,
+    logger: &Logger,
+) -> Result<()> {
+    let png = read_png_at(
+        path,
+        args.offset,
+        args.input.format,
+        args.oversize.allow_oversize,
+        logger,
+    )?;
+    let chunks = filter.shown_chunks(&png);
+
+    if args.json {
+        let chunks: Vec<Value> = chunks.iter().map(|c| chunk_json(c, true)).collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&chunks)?)?;
+    } else if args.hex {
+        for chunk in &chunks {
+            writeln!(out, "{} ({} bytes)", chunk.chunk_type(), chunk.length())?;
+            write!(out, "{}", hex_dump(chunk.data()))?;
+        }
+    } else {
+        writeln!(out, "PNG header: {:?}", png.header())?;
+        for chunk in &chunks {
+            writeln!(out, "{}", chunk)?;
+        }
+        if let (Ok(ihdr), false) = (png.ihdr(), args.only_ancillary) {
+            writeln!(out, "Image: {}", ihdr)?;
+        }
+        let sbit_shown = chunks.iter().any(|c| c.chunk_type() == "sBIT");
+        if let (Some(bits), true) = (png.significant_bits(), sbit_shown) {
+            let bits: Vec<String> = bits.iter().map(u8::to_string).collect();
+            writeln!(out, "Significant bits: {}", bits.join(", "))?;
+        }
+        for (keyword, text) in chunks.iter().copied().filter_map(text::text_entry) {
+            writeln!(out, "Text: {}: {}", keyword, text)?;
+        }
+    }
+    if args.show_trailing {
+        write_trailing(&png, out)?;
+    }
+
+    Ok(())
+}
+
+/// Which chunks `--only-critical`, `--only-ancillary` and `--type-filter`
+/// leave in view.
+struct ChunkFilter {
+    only_critical: bool,
+    only_ancillary: bool,
+    type_filter: Option<Regex>,
+}
+
+impl ChunkFilter {
+    fn new(only_critical: bool, only_ancillary: bool, type_filter: Option<&str>) -> Result<Self> {
+        let type_filter = type_filter
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| format!("invalid --type-filter '{}': {}", pattern, e))
+            })
+            .transpose()?;
+        Ok(ChunkFilter {
+            only_critical,
+            only_ancillary,
+            type_filter,
+        })
+    }
+
+    fn shows(&self, chunk: &Chunk) -> bool {
+        !(self.only_critical && chunk.is_ancillary() || self.only_ancillary && chunk.is_critical())
+            && self
+                .type_filter
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(&chunk.type_str()))
+    }
+
+    fn shown_chunks<'a>(&self, png: &'a Png) -> Vec<&'a Chunk> {
+        png.chunks().iter().filter(|c| self.shows(c)).collect()
+    }
+}
+
+pub fn diff(args: &DiffArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let left = read_png(&args.left, logger)?;
+    let right = read_png(&args.right, logger)?;
+
+    let mut differences = 0;
+    for change in align_chunks(left.chunks(), right.chunks()) {
+        match change {
+            ChunkChange::Same => continue,
+            ChunkChange::Removed(chunk) => writeln!(
+                out,
+                "- {} {:>10} {:08x}",
+                chunk.chunk_type(),
+                chunk.length(),
+                chunk.crc()
+            )?,
+            ChunkChange::Added(chunk) => writeln!(
+                out,
+                "+ {} {:>10} {:08x}",
+                chunk.chunk_type(),
+                chunk.length(),
+                chunk.crc()
+            )?,
+            ChunkChange::Modified(old, new) => writeln!(
+                out,
+                "~ {} {:>10} {:08x} -> {:08x} ({:+} bytes)",
+                new.chunk_type(),
+                new.length(),
+                old.crc(),
+                new.crc(),
+                new.length() as i64 - old.length() as i64
+            )?,
+        }
+        differences += 1;
+    }
+
+    if differences > 0 {
+        Err(format!("{} chunks differ", differences))?;
+    }
+    logger.info(out, format_args!("files have identical chunks"))?;
+
+    Ok(())
+}
+
+/// How a chunk from one file lines up with the other file's chunk list.
+enum ChunkChange<'a> {
+    Same,
+    Removed(&'a Chunk),
+    Added(&'a Chunk),
+    Modified(&'a Chunk, &'a Chunk),
+}
+
+/// Aligns two chunk lists on the longest common subsequence of their types,
+/// so a chunk whose type lines up but whose contents differ is reported as
+/// modified rather than as a removal plus an addition.
+fn align_chunks<'a>(left: &'a [Chunk], right: &'a [Chunk]) -> Vec<ChunkChange<'a>> {
+    let (n, m) = (left.len(), right.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if left[i].chunk_type() == right[j].chunk_type() {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && left[i].chunk_type() == right[j].chunk_type() {
+            if left[i] == right[j] {
+                changes.push(ChunkChange::Same);
+            } else {
+                changes.push(ChunkChange::Modified(&left[i], &right[j]));
+            }
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(ChunkChange::Removed(&left[i]));
+            i += 1;
+        } else {
+            changes.push(ChunkChange::Added(&right[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+pub fn strip(args: &StripArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let output = args.output.as_ref().unwrap_or(&args.filepath);
+    let logger = &piping_logger(logger, output, args.dry_run);
+    let mut png = read_png(&args.filepath, logger)?;
+    let keep = args
+        .keep
+        .iter()
+        .map(|t| ChunkType::from_str(t))
+        .collect::<Result<Vec<_>>>()?;
+
+    let removed = png.strip_ancillary(&keep);
+    warn_orphaned_frames(&removed, &png, logger);
+    if args.drop_trailing {
+        png.take_trailing_bytes();
+    }
+    let bytes: usize = removed.iter().map(Chunk::serialized_len).sum();
+    logger.info(
+        out,
+        format_args!("removed {} chunks ({} bytes)", removed.len(), bytes),
+    )?;
+
+    if !args.dry_run {
+        back_up(&args.filepath, output, &args.backup, logger)?;
+    }
+    write_png(&png, output, args.dry_run, out, logger)
+}
+
+pub fn merge(args: &MergeArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let types = args
+        .types
+        .iter()
+        .map(|t| ChunkType::from_str(t))
+        .collect::<Result<Vec<_>>>()?;
+    let output = args.output.as_ref().unwrap_or(&args.destination);
+    let logger = &piping_logger(logger, output, args.dry_run);
+    if let Some(critical) = types.iter().find(|t| t.is_critical()) {
+        Err(format!(
+            "{} is a critical chunk type and can't be merged",
+            critical
+        ))?;
+    }
+
+    // The source is never written, so its chunks can be moved out as they are
+    let mut source = read_png(&args.source, logger)?;
+    let copied: Vec<Chunk> = source
+        .strip_ancillary(&[])
+        .into_iter()
+        .filter(|chunk| types.contains(chunk.chunk_type()))
+        .collect();
+    let bytes: usize = copied.iter().map(Chunk::serialized_len).sum();
+    logger.info(
+        out,
+        format_args!("copied {} chunks ({} bytes)", copied.len(), bytes),
+    )?;
+
+    let mut png = read_png(&args.destination, logger)?;
+    for chunk in copied {
+        png.insert_before_iend(chunk);
+    }
+
+    if !args.dry_run {
+        back_up(&args.destination, output, &args.backup, logger)?;
+    }
+    write_png(&png, output, args.dry_run, out, logger)
+}
+
+/// With a backup suffix, copies `input` to its name plus the suffix before
+/// it is overwritten; nothing is copied when writing to a different file.
+/// An existing backup is only replaced with `--force`.
+fn back_up(input: &str, output: &str, options: &BackupArgs, logger: &Logger) -> Result<()> {
+    let Some(suffix) = &options.suffix else {
+        return Ok(());
+    };
+    // Standard input and a different output leave nothing to lose
+    if input != output || input == STDIO {
+        return Ok(());
+    }
+
+    let backup = format!("{}{}", input, suffix);
+    if !options.force && Path::new(&backup).exists() {
+        Err(format!(
+            "backup file {} already exists; pass --force to replace it",
+            backup
+        ))?;
+    }
+    fs::copy(input, &backup).map_err(|e| format!("{}: {}", backup, e))?;
+    logger.debug(format_args!("backed up {} to {}", input, backup));
+
+    Ok(())
+}
+
+/// The logger for a command writing its PNG to `output`: when that is
+/// stdout, messages go to stderr so they don't end up in the image.
+fn piping_logger(logger: &Logger, output: &str, dry_run: bool) -> Logger {
+    logger.with_info_on_stderr(output == STDIO && !dry_run)
+}
+
+/// Writes `png` to `output`, which is `out` for [`STDIO`], or with `dry_run`
+/// only reports the size and path it would have been written to.
+fn write_png(
+    png: &Png,
+    output: &str,
+    dry_run: bool,
+    out: &mut impl Write,
+    logger: &Logger,
+) -> Result<()> {
+    let total = png.total_size() as u64;
+    if dry_run {
+        writeln!(out, "would write {} bytes to {}", total, output)?;
+    } else if output == STDIO {
+        png.write_to(out)?;
+    } else {
+        let file = fs::File::create(output)?;
+        png.write_to_with_progress(file, |n| logger.progress("writing", n, total))?;
+    }
+    Ok(())
+}
+
+/// ANSI color codes for chunk listings.
+const CRITICAL_COLOR: &str = "36";
+const ANCILLARY_COLOR: &str = "33";
+const BAD_CRC_COLOR: &str = "31";
+
+/// Whether to color output written to stdout under `choice`.
+fn use_color(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+    }
+}
+
+/// Wraps `text` in the ANSI escape for `code` when coloring; an empty code
+/// leaves the text as it is.
+fn paint(text: impl Display, code: &str, color: bool) -> String {
+    if color && !code.is_empty() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Warns about each extra `IHDR` or `IEND` a leniently parsed file holds.
+fn warn_duplicate_ihdr_iend(png: &Png, logger: &Logger) {
+    for (name, offset) in png.duplicate_ihdr_iend() {
+        logger.warn(format_args!(
+            "duplicate {} chunk at offset {:#x}",
+            name, offset
+        ));
+    }
+}
+
+/// Warns when frame control chunks were removed while the frame data that
+/// depends on them is still in the file, which leaves a broken animation.
+fn warn_orphaned_frames(removed: &[Chunk], png: &Png, logger: &Logger) {
+    let fctl = removed.iter().filter(|c| c.chunk_type() == "fcTL").count();
+    let fdat = png
+        .chunks()
+        .iter()
+        .filter(|c| c.chunk_type() == "fdAT")
+        .count();
+    if fctl > 0 && fdat > 0 {
+        logger.warn(format_args!(
+            "removed {} fcTL chunks that {} remaining fdAT chunks depend on",
+            fctl, fdat
+        ));
+    }
+}
+
+/// Chunk types that carry textual metadata.
+const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+pub fn info(args: &InfoArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png(&args.filepath, logger)?;
+
+    writeln!(out, "{}", args.filepath)?;
+    if let Ok(ihdr) = png.ihdr() {
+        writeln!(out, "  dimensions:   {}x{}", ihdr.width, ihdr.height)?;
+        writeln!(out, "  bit depth:    {}", ihdr.bit_depth)?;
+        match ihdr.color_type_name() {
+            Some(name) => writeln!(out, "  color type:   {}", name)?,
+            None => writeln!(out, "  color type:   unknown ({})", ihdr.color_type)?,
+        }
+        match ihdr.interlace_name() {
+            Some(name) => writeln!(out, "  interlace:    {}", name)?,
+            None => writeln!(out, "  interlace:    unknown ({})", ihdr.interlace_method)?,
+        }
+    }
+    if let Some(problem) = png.palette_transparency_problem() {
+        logger.warn(format_args!("{}", problem));
+    }
+    match png.modification_time() {
+        Some(Ok(time)) => writeln!(out, "  modified:     {}", time)?,
+        Some(Err(e)) => writeln!(out, "  modified:     malformed tIME chunk ({})", e)?,
+        None => {}
+    }
+
+    let chunks = png.chunks();
+    let text = chunks
+        .iter()
+        .filter(|c| TEXT_CHUNK_TYPES.contains(&c.type_str().as_str()))
+        .count();
+    let ancillary = chunks.iter().filter(|c| c.is_ancillary()).count();
+    writeln!(out, "  chunks:       {}", chunks.len())?;
+    writeln!(out, "  text chunks:  {}", text)?;
+    for chunk in chunks {
+        // iTXt is parsed here rather than through text_entry to keep its language
+        let (keyword, text, language) = match &chunk.chunk_type().bytes() {
+            b"iTXt" => match text::parse_international_text(chunk.data()) {
+                Ok(itxt) => (itxt.keyword, itxt.text, itxt.language),
+                Err(_) => continue,
+            },
+            _ => match text::text_entry(chunk) {
+                Some((keyword, text)) => (keyword, text, String::new()),
+                None => continue,
+            },
+        };
+        if language.is_empty() {
+            writeln!(out, "    {}: {}", keyword, text)?;
+        } else {
+            writeln!(out, "    {} [{}]: {}", keyword, language, text)?;
+        }
+    }
+    writeln!(out, "  ancillary:    {}", ancillary)?;
+
+    let mut types: Vec<&ChunkType> = Vec::new();
+    for chunk in chunks {
+        if !types.contains(&chunk.chunk_type()) {
+            types.push(chunk.chunk_type());
+        }
+    }
+    let color = use_color(args.color);
+    writeln!(out, "  types:")?;
+    for chunk_type in types {
+        let code = if chunk_type.is_critical() {
+            CRITICAL_COLOR
+        } else {
+            ANCILLARY_COLOR
+        };
+        writeln!(out, "    {}", paint(chunk_type.describe(), code, color))?;
+    }
+
+    let control = chunks
+        .iter()
+        .filter(|c| c.chunk_type().apng_role() == Some("APNG control"))
+        .count();
+    let frame_data = chunks
+        .iter()
+        .filter(|c| c.chunk_type().apng_role() == Some("APNG frame data"))
+        .count();
+    if control + frame_data > 0 {
+        writeln!(
+            out,
+            "  animation:    {} APNG control, {} APNG frame data chunks",
+            control, frame_data
+        )?;
+    }
+    if args.show_trailing {
+        write_trailing(&png, out)?;
+    }
+
+    Ok(())
+}
+
+pub fn list(args: &ListArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let filter = ChunkFilter::new(
+        args.only_critical,
+        args.only_ancillary,
+        args.type_filter.as_deref(),
+    )?;
+    if args.jobs > 1 {
+        let list_one = |path: &str, out: &mut Vec<u8>| list_file(args, &filter, path, out, logger);
+        return for_each_file_ordered(&args.filepaths, args.jobs, list_one, |i, output, result| {
+            if args.filepaths.len() > 1 {
+                write_file_header(&args.filepaths[i], i, out)?;
+            }
+            out.write_all(output)?;
+            result
+        });
+    }
+    for_each_file(&args.filepaths, out, |path, out| {
+        list_file(args, &filter, path, out, logger)
+    })
+}
+
+fn list_file(
+    args: &ListArgs,
+    filter: &ChunkFilter,
+    path: &str,
+    out: &mut impl Write,
+    logger: &Logger,
+) -> Result<()> {
+    let options = ParseOptions {
+        allow_oversize: args.oversize.allow_oversize,
+        recover: args.recover,
+        ..args.input.format.parse_options(ParseOptions::lenient())
+    };
+    let png = read_png_at_with(path, args.offset, &options, logger)?;
+    warn_duplicate_ihdr_iend(&png, logger);
+    let chunks = filter.shown_chunks(&png);
+    for chunk in &chunks {
+        if !chunk.crc_ok() {
+            let expected = Chunk::crc_of(chunk.chunk_type(), chunk.data());
+            logger.warn(format_args!(
+                "{} chunk has a bad CRC: stored {:08x}, expected {:08x}",
+                chunk.chunk_type(),
+                chunk.crc(),
+                expected
+            ));
+        }
+    }
+
+    if args.json {
+        let chunks: Vec<Value> = chunks.iter().map(|c| chunk_json(c, false)).collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&chunks)?)?;
+        return Ok(());
+    }
+
+    let color = use_color(args.color);
+    for chunk in chunks {
+        let type_color = if chunk.is_critical() {
+            CRITICAL_COLOR
+        } else {
+            ANCILLARY_COLOR
+        };
+        let crc_color = if chunk.crc_ok() { "" } else { BAD_CRC_COLOR };
+        write!(
+            out,
+            "{} {:>10} {}",
+            paint(chunk.chunk_type(), type_color, color),
+            chunk.length(),
+            paint(format!("{:08x}", chunk.crc()), crc_color, color)
+        )?;
+        match (text::text_entry(chunk), chunk.chunk_type().apng_role()) {
+            (Some((keyword, text)), _) => writeln!(out, "  {}: {}", keyword, text)?,
+            (None, Some(role)) => writeln!(out, "  {}", role)?,
+            _ if chunk.chunk_type() == "tIME" => match Time::try_from(chunk.data()) {
+                Ok(time) => writeln!(out, "  {}", time)?,
+                Err(e) => writeln!(out, "  malformed: {}", e)?,
+            },
+            (None, None) => writeln!(out, "  {}", chunk.chunk_type().describe())?,
+        }
+    }
+
+    Ok(())
+}
+
+pub fn typeinfo(args: &TypeinfoArgs, out: &mut impl Write, _logger: &Logger) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let bytes = chunk_type.bytes();
+
+    let decimal: Vec<String> = bytes.iter().map(|b| b.to_string()).collect();
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    writeln!(out, "{}", chunk_type)?;
+    writeln!(out, "  bytes:        {}", decimal.join(" "))?;
+    writeln!(out, "  hex:          {}", hex.join(" "))?;
+    writeln!(
+        out,
+        "  criticality:  {}",
+        if chunk_type.is_critical() {
+            "critical"
+        } else {
+            "ancillary"
+        }
+    )?;
+    writeln!(
+        out,
+        "  visibility:   {}",
+        if chunk_type.is_public() {
+            "public"
+        } else {
+            "private"
+        }
+    )?;
+    writeln!(
+        out,
+        "  reserved bit: {}",
+        if chunk_type.is_reserved_bit_valid() {
+            "valid"
+        } else {
+            "invalid"
+        }
+    )?;
+    writeln!(
+        out,
+        "  copying:      {}",
+        if chunk_type.is_safe_to_copy() {
+            "safe to copy"
+        } else {
+            "unsafe to copy"
+        }
+    )?;
+    if let Some(description) = chunk_type.standard_description() {
+        writeln!(out, "  description:  {}", description)?;
+    }
+
+    Ok(())
+}
+
+pub fn chunk_types(_args: &ChunkTypesArgs, out: &mut impl Write, _logger: &Logger) -> Result<()> {
+    for (chunk_type, description) in STANDARD_TYPES {
+        let class = if chunk_type.is_critical() {
+            "critical"
+        } else {
+            "ancillary"
+        };
+        writeln!(out, "{}  {:<9}  {}", chunk_type, class, description)?;
+    }
+
+    Ok(())
+}
+
+/// Quotes `word` for a POSIX shell, so a suggested command does the same
+/// thing when pasted whatever characters it holds.
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./,:=@%+".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+pub fn quickstart(args: &QuickstartArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = match &args.filepath {
+        Some(filepath) => read_png(filepath, logger)?,
+        None => Png::default(),
+    };
+    let chunk_type = auto_chunk_type(&png, args.seed);
+
+    writeln!(out, "suggested chunk type: {}", chunk_type.describe())?;
+    writeln!(out)?;
+    writeln!(out, "The case of each letter sets one property:")?;
+    writeln!(out, "  1st  lowercase: ancillary, so viewers can skip it")?;
+    writeln!(
+        out,
+        "  2nd  lowercase: private, not defined by the PNG specification"
+    )?;
+    writeln!(out, "  3rd  uppercase: required in every valid chunk type")?;
+    writeln!(
+        out,
+        "  4th  lowercase: safe to copy when an editor changes the image"
+    )?;
+    writeln!(out)?;
+
+    let file = args.filepath.as_deref().unwrap_or("FILE");
+    writeln!(out, "To hide a message:")?;
+    writeln!(
+        out,
+        "  pngme encode {} {} {}",
+        shell_quote(file),
+        chunk_type,
+        shell_quote(&args.message)
+    )?;
+    writeln!(out, "To read it back:")?;
+    writeln!(out, "  pngme decode {} {}", shell_quote(file), chunk_type)?;
+
+    let Some(filepath) = &args.filepath else {
+        return Ok(());
+    };
+    writeln!(out)?;
+    let encode_args = EncodeArgs {
+        filepath: filepath.clone(),
+        chunk_type: Some(chunk_type.to_string()),
+        message: Some(args.message.clone()),
+        ..Default::default()
+    };
+    encode(&encode_args, out, logger)?;
+    writeln!(
+        out,
+        "hid the message in {} as a {} chunk",
+        filepath, chunk_type
+    )?;
+    Ok(())
+}
+
+pub fn count(args: &CountArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let mut file = png::open_file(Path::new(&args.filepath))?;
+    file.seek(SeekFrom::Start(args.offset))?;
+    let total = file.metadata()?.len().saturating_sub(args.offset);
+    let progress_logger = *logger;
+    let (reader, mut offset) = match args.input.format {
+        InputFormat::Png => (
+            ChunkReader::from_png(BufReader::new(file))?,
+            args.offset as usize + Png::STANDARD_HEADER.len(),
+        ),
+        InputFormat::Chunks => (ChunkReader::new(BufReader::new(file)), args.offset as usize),
+    };
+    let reader = reader.with_progress(move |n| progress_logger.progress("reading", n, total));
+
+    // Chunk count and serialized byte size per type
+    let mut counts: HashMap<ChunkType, (usize, u64)> = HashMap::new();
+    for chunk in reader {
+        let chunk = chunk?;
+        log_chunk(&chunk, offset, logger);
+        offset += chunk.serialized_len();
+        let entry = counts.entry(chunk.chunk_type().clone()).or_default();
+        entry.0 += 1;
+        entry.1 += chunk.serialized_len() as u64;
+    }
+
+    let mut rows: Vec<(String, usize, u64)> = counts
+        .into_iter()
+        .map(|(chunk_type, (count, size))| (chunk_type.to_string(), count, size))
+        .collect();
+    rows.sort();
+
+    writeln!(out, "{:<6} {:>8} {:>12}", "type", "count", "bytes")?;
+    for (chunk_type, count, size) in &rows {
+        writeln!(out, "{:<6} {:>8} {:>12}", chunk_type, count, size)?;
+    }
+
+    let total_count: usize = rows.iter().map(|(_, count, _)| count).sum();
+    let total_size: u64 = rows.iter().map(|(_, _, size)| size).sum();
+    writeln!(out, "{:<6} {:>8} {:>12}", "total", total_count, total_size)?;
+
+    Ok(())
+}
+
+pub fn extract(args: &ExtractArgs, _out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png_at(
+        &args.filepath,
+        args.offset,
+        args.input.format,
+        args.oversize.allow_oversize,
+        logger,
+    )?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+
+    let chunk = png
+        .chunks_by_type(&chunk_type.to_string())
+        .nth(args.index)
+        .ok_or_else(|| {
+            format!(
+                "chunk type '{}' not found at index {}",
+                chunk_type, args.index
+            )
+        })?;
+
+    fs::write(&args.output, chunk.data())?;
+
+    Ok(())
+}
+
+/// The error `verify --check-only` ends with when any file didn't pass,
+/// carrying the exit status to report it with: 1 when every file could be
+/// read but some failed, 2 when some couldn't be read at all.
+#[derive(Debug)]
+pub struct CheckFailed {
+    pub exit_code: u8,
+    summary: String,
+}
+
+impl Display for CheckFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.summary)
+    }
+}
+
+impl std::error::Error for CheckFailed {}
+
+pub fn verify(args: &VerifyArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    if args.check_only {
+        return check_files(&args.filepaths, out);
+    }
+    if let [path] = args.filepaths.as_slice() {
+        return verify_file(path, out, logger);
+    }
+
+    let mut failures = 0;
+    let mut report = |path: &str, result: Result<()>| {
+        if let Err(e) = result {
+            logger.warn(format_args!("{}: {}", path, e));
+            failures += 1;
+        }
+    };
+    if args.jobs > 1 {
+        let verify_one = |path: &str, out: &mut Vec<u8>| verify_file(path, out, logger);
+        for_each_file_ordered(
+            &args.filepaths,
+            args.jobs,
+            verify_one,
+            |i, output, result| {
+                write_file_header(&args.filepaths[i], i, out)?;
+                out.write_all(output)?;
+                report(&args.filepaths[i], result);
+                Ok(())
+            },
+        )?;
+    } else {
+        for (i, path) in args.filepaths.iter().enumerate() {
+            write_file_header(path, i, out)?;
+            report(path, verify_file(path, out, logger));
+        }
+    }
+    if failures > 0 {
+        Err(format!(
+            "{} of {} files failed verification",
+            failures,
+            args.filepaths.len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// `verify --check-only`: one stable line per file and a summary.
+fn check_files(paths: &[String], out: &mut impl Write) -> Result<()> {
+    let paths = expand_directories(paths)?;
+    let (mut failed, mut unreadable) = (0, 0);
+    for path in &paths {
+        match check_file(path) {
+            Ok(None) => writeln!(out, "pass {}", path)?,
+            Ok(Some(problem)) => {
+                failed += 1;
+                writeln!(out, "fail {}: {}", path, problem)?
+            }
+            Err(e) => {
+                unreadable += 1;
+                writeln!(out, "unreadable {}: {}", path, e)?
+            }
+        }
+    }
+    let passed = paths.len() - failed - unreadable;
+    let summary = format!(
+        "{} passed, {} failed, {} unreadable",
+        passed, failed, unreadable
+    );
+    writeln!(out, "summary: {}", summary)?;
+
+    let exit_code = match (failed, unreadable) {
+        (0, 0) => return Ok(()),
+        (_, 0) => 1,
+        _ => 2,
+    };
+    Err(CheckFailed { exit_code, summary })?
+}
+
+/// `paths` with each directory replaced by the `*.png` files directly inside
+/// it, in name order.
+fn expand_directories(paths: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            expanded.push(path.clone());
+            continue;
+        }
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path).map_err(|e| format!("{}: {}", path, e))? {
+            let entry = entry?.path();
+            let is_png = entry
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+            if is_png && entry.is_file() {
+                entries.push(entry.to_string_lossy().into_owned());
+            }
+        }
+        entries.sort();
+        expanded.extend(entries);
+    }
+    Ok(expanded)
+}
+
+/// The first chunk of `path` whose CRC is wrong, described, or `None` when
+/// every one is right.
+fn check_file(path: &str) -> Result<Option<String>> {
+    let png = Png::from_bytes_with(&read_file(path)?, &ParseOptions::lenient())?;
+    Ok(png
+        .chunks()
+        .iter()
+        .find(|chunk| !chunk.crc_ok())
+        .map(|chunk| {
+            format!(
+                "{} stored {:08x}, expected {:08x}",
+                chunk.chunk_type(),
+                chunk.crc(),
+                Chunk::crc_of(chunk.chunk_type(), chunk.data())
+            )
+        }))
+}
+
+fn verify_file(path: &str, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = Png::from_bytes_with(&read_file(path)?, &ParseOptions::lenient())?;
+    log_chunks(&png, 0, logger);
+    warn_duplicate_ihdr_iend(&png, logger);
+    if let Some(problem) = png.palette_transparency_problem() {
+        logger.warn(format_args!("{}", problem));
+    }
+
+    let mut failures = 0;
+    for chunk in png.chunks() {
+        if chunk.crc_ok() {
+            writeln!(out, "ok   {} {:08x}", chunk.chunk_type(), chunk.crc())?;
+        } else {
+            let expected = Chunk::crc_of(chunk.chunk_type(), chunk.data());
+            failures += 1;
+            writeln!(
+                out,
+                "FAIL {} stored {:08x}, expected {:08x}",
+                chunk.chunk_type(),
+                chunk.crc(),
+                expected
+            )?;
+        }
+    }
+
+    if failures > 0 {
+        Err(format!(
+            "{} of {} chunks failed CRC verification",
+            failures,
+            png.chunks().len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+pub fn fix(args: &FixArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let options = ParseOptions {
+        recover: args.recover,
+        ..ParseOptions::lenient()
+    };
+    let logger = &piping_logger(logger, &args.output, false);
+    let mut png = read_png_at_with(&args.filepath, 0, &options, logger)?;
+
+    let changed = png.recalculate_crcs();
+    logger.info(
+        out,
+        format_args!("fixed {} of {} chunk CRCs", changed, png.chunks().len()),
+    )?;
+    write_png(&png, &args.output, false, out, logger)
+}
+
+/// Rewrites the file in canonical form, so that files differing only in the
+/// order of their ancillary chunks produce the same bytes, as long as unknown
+/// chunks sit between the same critical chunks; running it on its own output
+/// changes nothing.
+pub fn normalize(args: &NormalizeArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let output = args.output.as_ref().unwrap_or(&args.filepath);
+    let logger = &piping_logger(logger, output, false);
+    let mut png = read_png_at_with(&args.filepath, 0, &ParseOptions::lenient(), logger)?;
+    let (fixed, dropped) = normalize_png(&mut png);
+    logger.info(
+        out,
+        format_args!(
+            "fixed {} chunk CRCs, dropped {} trailing bytes",
+            fixed, dropped
+        ),
+    )?;
+
+    back_up(&args.filepath, output, &args.backup, logger)?;
+    write_png(&png, output, false, out, logger)
+}
+
+/// Puts `png` in canonical form, returning how many CRCs were fixed and how
+/// many trailing bytes were dropped.
+fn normalize_png(png: &mut Png) -> (usize, usize) {
+    png.reorder_canonical();
+    let fixed = png.recalculate_crcs();
+    let dropped = png.take_trailing_bytes().len();
+    (fixed, dropped)
+}
+
+pub fn hash(args: &HashArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let bytes = if args.normalize {
+        let mut png = read_png_at_with(&args.filepath, 0, &ParseOptions::lenient(), logger)?;
+        normalize_png(&mut png);
+        png.as_bytes()
+    } else {
+        read_file(&args.filepath)?
+    };
+
+    let digest = match args.algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+        HashAlgorithm::Crc32 => format!("{:08x}", PNG_CRC.checksum(&bytes)),
+    };
+    writeln!(out, "{}  {}", digest, args.filepath)?;
+
+    Ok(())
+}
+
+pub fn relabel(args: &RelabelArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let old_type = ChunkType::from_str(&args.old_type)?;
+    let new_type = ChunkType::from_str(&args.new_type)?;
+    check_chunk_type(&new_type, false, logger)?;
+    let logger = &piping_logger(logger, &args.filepath, args.dry_run);
+
+    let mut png = read_png(&args.filepath, logger)?;
+    let relabelled = png.relabel_chunks(&old_type, &new_type);
+    if relabelled == 0 {
+        Err(format!("no {} chunks found", old_type))?;
+    }
+    logger.info(
+        out,
+        format_args!(
+            "relabelled {} {} chunks as {}",
+            relabelled, old_type, new_type
+        ),
+    )?;
+
+    if !args.dry_run {
+        back_up(&args.filepath, &args.filepath, &args.backup, logger)?;
+    }
+    write_png(&png, &args.filepath, args.dry_run, out, logger)
+}
+
+pub fn archive(args: &ArchiveArgs, _out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png_at(
+        &args.filepath,
+        args.offset,
+        args.input.format,
+        args.oversize.allow_oversize,
+        logger,
+    )?;
+    let bytes: Vec<u8> = png
+        .chunks()
+        .iter()
+        .flat_map(|chunk| chunk.as_bytes())
+        .collect();
+    fs::write(&args.output, bytes)?;
+
+    Ok(())
+}
+
+pub fn unarchive(args: &UnarchiveArgs, _out: &mut impl Write, _logger: &Logger) -> Result<()> {
+    let bytes = fs::read(&args.archive)?;
+    let png = Png::from_chunks(Png::parse_chunks(&bytes, &ParseOptions::default())?);
+    png.write_file(&args.output)?;
+
+    Ok(())
+}
+
+pub fn scan(args: &ScanArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png(&args.filepath, logger)?;
+    let found = scan_chunks(&png, 1, args.max_depth, out)?;
+    logger.info(out, format_args!("found {} embedded PNGs", found))?;
+
+    Ok(())
+}
+
+pub fn entropy(args: &EntropyArgs, out: &mut impl Write, logger: &Logger) -> Result<()> {
+    let png = read_png(&args.filepath, logger)?;
+    for chunk in png.chunks() {
+        let entropy = shannon_entropy(chunk.data());
+        write!(
+            out,
+            "{} {:>10} {:.3}",
+            chunk.chunk_type(),
+            chunk.length(),
+            entropy
+        )?;
+        if entropy > args.threshold {
+            write!(out, "  likely compressed or encrypted")?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Shannon entropy of `data` in bits per byte: 0 when every byte is the
+/// same, approaching 8 for data that looks random.
+fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// Lists the chunks of `png`, indented by `depth`, along with any PNG found
+/// inside their data while `depth` is below `max_depth`. Returns how many
+/// embedded PNGs were found.
+fn scan_chunks(png: &Png, depth: usize, max_depth: usize, out: &mut impl Write) -> Result<usize> {
+    let indent = "  ".repeat(depth - 1);
+    let mut found = 0;
+
+    for chunk in png.chunks() {
+        writeln!(out, "{}{} {}", indent, chunk.chunk_type(), chunk.length())?;
+        if depth >= max_depth {
+            continue;
+        }
+
+        let data = chunk.data();
+        let signature = Png::STANDARD_HEADER;
+        for start in (0..data.len()).filter(|i| data[*i..].starts_with(&signature)) {
+            if let Ok(inner) = Png::from_bytes(&data[start..]) {
+                writeln!(out, "{}  embedded PNG at byte {}:", indent, start)?;
+                found += 1 + scan_chunks(&inner, depth + 1, max_depth, out)?;
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Runs `f` on each of `paths`, putting a `==> path <==` header before each
+/// file's output when there is more than one.
+fn for_each_file<W: Write>(
+    paths: &[String],
+    out: &mut W,
+    mut f: impl FnMut(&str, &mut W) -> Result<()>,
+) -> Result<()> {
+    for (i, path) in paths.iter().enumerate() {
+        if paths.len() > 1 {
+            write_file_header(path, i, out)?;
+        }
+        f(path, out)?;
+    }
+    Ok(())
+}
+
+/// Runs `f` on each of `paths` on up to `jobs` threads, each file writing
+/// into a buffer of its own. `emit` gets each file's index, output and result
+/// in the order of `paths`, as soon as that file and every one before it are
+/// done; an error from `emit` stops any files not yet started.
+fn for_each_file_ordered(
+    paths: &[String],
+    jobs: usize,
+    f: impl Fn(&str, &mut Vec<u8>) -> Result<()> + Sync,
+    mut emit: impl FnMut(usize, &[u8], Result<()>) -> Result<()>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(paths.len()) {
+            let sender = sender.clone();
+            let (next, stop, f) = (&next, &stop, &f);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(i) else { break };
+                    let mut output = Vec::new();
+                    // Errors aren't Send, so they cross back as their message
+                    let result = f(path, &mut output).map_err(|e| e.to_string());
+                    if sender.send((i, output, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = HashMap::new();
+        let mut emitted = 0;
+        for (i, output, result) in receiver {
+            pending.insert(i, (output, result));
+            while let Some((output, result)) = pending.remove(&emitted) {
+                if let Err(e) = emit(emitted, &output, result.map_err(Error::from)) {
+                    stop.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+                emitted += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn write_file_header(path: &str, index: usize, out: &mut impl Write) -> Result<()> {
+    if index > 0 {
+        writeln!(out)?;
+    }
+    writeln!(out, "==> {} <==", path)?;
+    Ok(())
+}
+
+/// How many bytes of trailing data `--show-trailing` previews.
+const TRAILING_PREVIEW: usize = 64;
+
+fn write_trailing(png: &Png, out: &mut impl Write) -> Result<()> {
+    let trailing = png.trailing_bytes();
+    if trailing.is_empty() {
+        writeln!(out, "no data after IEND")?;
+        return Ok(());
+    }
+
+    writeln!(out, "{} bytes after IEND:", trailing.len())?;
+    let preview = &trailing[..trailing.len().min(TRAILING_PREVIEW)];
+    write!(out, "{}", hex_dump(preview))?;
+    if trailing.len() > TRAILING_PREVIEW {
+        writeln!(out, "...")?;
+    }
+    Ok(())
+}
+
+/// Renders `data` like `xxd`: an offset column, 16 bytes per row in hex, and
+/// an ASCII gutter where anything unprintable is shown as `.`.
+fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (row, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {:<47}  {}\n",
+            row * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    dump
+}
+
+/// JSON description of a chunk. Keys are always emitted in sorted order so
+/// the output is stable and diffable.
+fn chunk_json(chunk: &Chunk, include_data: bool) -> Value {
+    let chunk_type = chunk.chunk_type();
+    let mut value = json!({
+        "type": chunk_type.to_string(),
+        "length": chunk.length(),
+        "crc": format!("{:08x}", chunk.crc()),
+        "critical": chunk_type.is_critical(),
+        "public": chunk_type.is_public(),
+        "safe_to_copy": chunk_type.is_safe_to_copy(),
+    });
+    if include_data {
+        value["data"] = Value::String(STANDARD.encode(chunk.data()));
+    }
+    if let Some((keyword, text)) = text::text_entry(chunk) {
+        value["keyword"] = Value::String(keyword);
+        value["text"] = Value::String(text);
+    }
+    value
+}
+
+/// The chunks carrying the message: one, or several parts with `--split`.
+fn message_chunks(chunk_type: ChunkType, args: &EncodeArgs) -> Result<Vec<Chunk>> {
+    let message = message_bytes(args)?;
+    match args.split {
+        Some(max_len) => split::split_payload(&chunk_type, &message, max_len),
+        None => Ok(vec![Chunk::new(chunk_type, message)?]),
+    }
+}
+
+fn message_bytes(args: &EncodeArgs) -> Result<Vec<u8>> {
+    let data = match (&args.message, &args.message_file, args.message_stdin) {
+        (None, None, false) if !args.pack.is_empty() => pack_messages(&args.pack)?,
+        (Some(message), None, false) => message.as_bytes().to_vec(),
+        (None, Some(path), false) => {
+            if args.split.is_none() {
+                check_message_length(fs::metadata(path)?.len())?;
+            }
+            fs::read(path)?
+        }
+        (None, None, true) if args.filepath == STDIO => {
+            Err("the PNG and the message can't both come from standard input")?
+        }
+        (None, None, true) => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            data
+        }
+        _ => {
+            Err("exactly one of a message, --message-file, --message-stdin or --pack is required")?
+        }
+    };
+
+    let data = if args.base64 {
+        decode_base64(&data)?
+    } else if args.encoding == TextEncoding::Latin1 {
+        text::string_to_latin1(std::str::from_utf8(&data).map_err(|_| "not valid utf8")?)?
+    } else {
+        data
+    };
+    let data = if args.verified {
+        integrity::seal(&data)?
+    } else {
+        data
+    };
+
+    if args.compress {
+        return text::compress_text(&args.keyword, &data, args.compression_method);
+    }
+
+    Ok(data)
+}
+
+/// Packs `name=message` arguments into one buffer of named entries.
+fn pack_messages(entries: &[String]) -> Result<Vec<u8>> {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let (name, message) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("pack entry '{}' must be name=message", entry))?;
+            Ok((name.to_string(), message.as_bytes().to_vec()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    pack::pack_entries(&entries)
+}
+
+/// Rejects a message file too long for a chunk before reading it into memory.
+fn check_message_length(len: u64) -> Result<()> {
+    if len > Chunk::MAX_LENGTH as u64 {
+        Err(format!(
+            "message is {} bytes, more than the {} a chunk can hold",
+            len,
+            Chunk::MAX_LENGTH
+        ))?;
+    }
+    Ok(())
+}
+
+/// Builds the chunks described by a JSON spec file, validating every entry
+/// before any of them are applied.
+fn spec_chunks(spec: &str) -> Result<Vec<Chunk>> {
+    let entries: Vec<Value> = serde_json::from_slice(&fs::read(spec)?)
+        .map_err(|e| format!("invalid spec file '{}': {}", spec, e))?;
+
+    let mut chunks = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let chunk = spec_chunk(entry).map_err(|e| format!("spec entry {}: {}", i, e))?;
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Builds the chunk for one spec entry of the form
+/// `{ "type": "tEXt", "message": "...", "base64": false }`.
+fn spec_chunk(entry: &Value) -> Result<Chunk> {
+    let chunk_type = entry
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or("missing string field 'type'")?;
+    let chunk_type =
+        ChunkType::from_str(chunk_type).map_err(|e| format!("invalid chunk type: {}", e))?;
+
+    let message = entry
+        .get("message")
+        .and_then(Value::as_str)
+        .ok_or("missing string field 'message'")?;
+
+    let base64 = match entry.get("base64") {
+        None => false,
+        Some(value) => value.as_bool().ok_or("field 'base64' must be a boolean")?,
+    };
+
+    let data = if base64 {
+        decode_base64(message.as_bytes())?
+    } else {
+        message.as_bytes().to_vec()
+    };
+
+    Chunk::new(chunk_type, data)
+}
+
+/// Explanation of the case bits, appended to chunk type warnings and errors.
+const CASE_BITS_HELP: &str = "the case of each letter carries a property: \
+    1st uppercase = critical, 2nd uppercase = public, \
+    3rd must be uppercase (reserved), 4th lowercase = safe to copy";
+
+/// Warns about, or under `strict` refuses, a chunk type that breaks the PNG
+/// naming rules or is critical, since decoders reject images holding a
+/// critical chunk they don't know and a standard one would corrupt the image.
+fn check_chunk_type(chunk_type: &ChunkType, strict: bool, logger: &Logger) -> Result<()> {
+    let mut problems = Vec::new();
+    if !chunk_type.is_valid() {
+        problems.push(format!(
+            "chunk type '{}' has an invalid reserved bit (the third letter must be uppercase)",
+            chunk_type
+        ));
+    }
+    if chunk_type.is_critical() {
+        let suggestion = ChunkType::new(chunk_type.bytes(), false, false, true, true)?;
+        problems.push(format!(
+            "chunk type '{}' is critical (the first letter is uppercase) and will \
+             corrupt the image; use a private ancillary type such as '{}' instead",
+            chunk_type, suggestion
+        ));
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let problem = format!("{}; {}", problems.join("; "), CASE_BITS_HELP);
+    if strict {
+        return Err(problem.into());
+    }
+    logger.warn(format_args!("{}", problem));
+
+    Ok(())
+}
+
+/// Picks a private, ancillary, safe-to-copy chunk type with a valid reserved
+/// bit that is not already used in the file.
+fn auto_chunk_type(png: &Png, seed: Option<u64>) -> ChunkType {
+    let mut rng = seed.map(Rng::new).unwrap_or_else(Rng::from_time);
+
+    loop {
+        let mut letter = || b'a' + rng.below(26) as u8;
+        let bytes = [letter(), letter(), letter().to_ascii_uppercase(), letter()];
+
+        let chunk_type = ChunkType::try_from(bytes).expect("letters are a valid chunk type");
+        if !png
+            .chunks()
+            .iter()
+            .any(|chunk| chunk.chunk_type() == &chunk_type)
+        {
+            return chunk_type;
+        }
+    }
+}
+
+/// Parses the 16 hex digits given to `--signature` into the 8 bytes they
+/// stand for.
+fn parse_signature(hex: &str) -> Result<[u8; 8]> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Err(format!("signature '{}' is not valid hex", hex))?;
+    }
+    if hex.len() != 16 {
+        Err(format!(
+            "signature must be 8 bytes (16 hex digits), got {} digits",
+            hex.len()
+        ))?;
+    }
+
+    let mut signature = [0; 8];
+    for (i, byte) in signature.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+    }
+    Ok(signature)
+}
+
+fn decode_base64(data: &[u8]) -> Result<Vec<u8>> {
+    let data = STANDARD
+        .decode(data.trim_ascii())
+        .map_err(|e| format!("message is not valid base64: {}", e))?;
+    Ok(data)
+}
+
+fn read_png(filepath: &str, logger: &Logger) -> Result<Png> {
+    read_png_at(filepath, 0, InputFormat::Png, false, logger)
+}
+
+/// Reads a PNG that starts `offset` bytes into the file.
+fn read_png_at(
+    filepath: &str,
+    offset: u64,
+    input_format: InputFormat,
+    allow_oversize: bool,
+    logger: &Logger,
+) -> Result<Png> {
+    let options = ParseOptions {
+        allow_oversize,
+        ..input_format.parse_options(ParseOptions::default())
+    };
+    read_png_at_with(filepath, offset, &options, logger)
+}
+
+fn read_png_at_with(
+    filepath: &str,
+    offset: u64,
+    options: &ParseOptions,
+    logger: &Logger,
+) -> Result<Png> {
+    let bytes = read_file(filepath)?;
+    let start = usize::try_from(offset)
+        .ok()
+        .filter(|start| *start <= bytes.len())
+        .ok_or_else(|| format!("offset {} is past the end of the file", offset))?;
+    logger.debug(format_args!("read {} bytes from {}", bytes.len(), filepath));
+
+    let png = Png::from_bytes_with(&bytes[start..], options)?;
+    if options.expect_signature {
+        log_chunks(&png, start, logger);
+    } else {
+        // Chunk offsets count a signature, which a chunk stream doesn't have
+        for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+            log_chunk(chunk, start + offset - Png::STANDARD_HEADER.len(), logger);
+        }
+    }
+    for (offset, len) in png.skipped_regions() {
+        logger.warn(format_args!(
+            "skipped {} damaged bytes at offset {:#x}",
+            len,
+            start + offset
+        ));
+    }
+    for chunk in png.chunks() {
+        if chunk.length() > Chunk::MAX_LENGTH {
+            logger.warn(format_args!(
+                "{} chunk holds {} bytes, over the PNG maximum of {}",
+                chunk.chunk_type(),
+                chunk.length(),
+                Chunk::MAX_LENGTH
+            ));
+        }
+    }
+
+    Ok(png)
+}
+
+/// Reads the whole of `filepath`, or of standard input for `-`; a PNG can't
+/// be parsed until all of it is in.
+fn read_file(filepath: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if filepath == STDIO {
+        io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        png::open_file(Path::new(filepath))?.read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Logs where each chunk of a PNG read from `start` bytes into its file sits.
+fn log_chunks(png: &Png, start: usize, logger: &Logger) {
+    for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+        log_chunk(chunk, start + offset, logger);
+    }
+}
+
+fn log_chunk(chunk: &Chunk, offset: usize, logger: &Logger) {
+    logger.debug(format_args!(
+        "{} at offset {}: length {}, crc {:08x}",
+        chunk.chunk_type(),
+        offset,
+        chunk.length(),
+        chunk.crc()
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::InputFormatArgs;
+    use tempfile::TempDir;
+
+    fn read_png(filepath: &str) -> Result<Png> {
+        super::read_png(filepath, &Logger::default())
+    }
+
+    fn testing_file(dir: &TempDir) -> String {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]).unwrap(),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3]).unwrap(),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap(),
+        ];
+
+        let path = dir.path().join("test.png");
+        fs::write(&path, Png::from_chunks(chunks).as_bytes()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn encode_args(filepath: &str, message: &str) -> EncodeArgs {
+        EncodeArgs {
+            filepath: filepath.to_string(),
+            chunk_type: Some("ruSt".to_string()),
+            message: Some(message.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn decode_args(filepath: &str) -> DecodeArgs {
+        DecodeArgs {
+            filepath: filepath.to_string(),
+            chunk_type: "ruSt".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+
+        let mut out = Vec::new();
+        encode(&encode_args(&file, "hello"), &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"file will grow by 17 bytes\n");
+
+        let mut out = Vec::new();
+        decode(&decode_args(&file), &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"hello\n");
+    }
+
+    #[test]
+    fn test_decode_large_text_chunk() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let message = "año ".repeat(500_000);
+        let mut png = read_png(&file).unwrap();
+        png.encode("ruSt", &message).unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        decode(&decode_args(&file), &mut out, &Logger::default()).unwrap();
+        assert_eq!(out.len(), message.len() + 1);
+        assert_eq!(out, format!("{}\n", message).as_bytes());
+    }
+
+    #[test]
+    fn test_encode_twice_stores_once() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = encode_args(&file, "hello");
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let written = fs::read(&file).unwrap();
+
+        let mut out = Vec::new();
+        encode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"message already present, nothing to do\n");
+        assert_eq!(fs::read(&file).unwrap(), written);
+
+        encode(
+            &encode_args(&file, "other"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        let args = EncodeArgs {
+            allow_duplicate: true,
+            ..args
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let png = read_png(&file).unwrap();
+        let messages: Vec<&[u8]> = png.chunks_by_type("ruSt").map(Chunk::data).collect();
+        assert_eq!(messages, [&b"hello"[..], b"other", b"hello"]);
+    }
+
+    #[test]
+    fn test_encode_append_twice_repeats_message() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            append_to_existing: true,
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let mut out = Vec::new();
+        encode(&args, &mut out, &Logger::default()).unwrap();
+
+        assert_eq!(out, b"file will grow by 5 bytes\n");
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hellohello");
+    }
+
+    #[test]
+    fn test_encode_decode_latin1() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            encoding: TextEncoding::Latin1,
+            ..encode_args(&file, "año")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"a\xF1o");
+
+        let mut out = Vec::new();
+        let args = DecodeArgs {
+            encoding: TextEncoding::Latin1,
+            ..decode_args(&file)
+        };
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, "año\n".as_bytes());
+
+        let err = decode(&decode_args(&file), &mut Vec::new(), &Logger::default()).err();
+        assert_eq!(err.unwrap().to_string(), "not valid utf8");
+        let args = EncodeArgs {
+            encoding: TextEncoding::Latin1,
+            ..encode_args(&file, "5€")
+        };
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).err();
+        assert_eq!(err.unwrap().to_string(), "'€' can't be written in Latin-1");
+    }
+
+    #[test]
+    fn test_encode_decode_base64_with_null_bytes() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let payload = STANDARD.encode([0, 1, 0, 255, 0]);
+
+        let args = EncodeArgs {
+            base64: true,
+            ..encode_args(&file, &payload)
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let png = read_png(&file).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            &[0, 1, 0, 255, 0]
+        );
+
+        let mut out = Vec::new();
+        let args = DecodeArgs {
+            base64: true,
+            ..decode_args(&file)
+        };
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, format!("{}\n", payload).as_bytes());
+    }
+
+    #[test]
+    fn test_encode_invalid_base64() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            base64: true,
+            ..encode_args(&file, "not base64!")
+        };
+
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err.to_string().contains("not valid base64"));
+    }
+
+    #[test]
+    fn test_encode_message_file() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let message_path = dir.path().join("message.bin");
+        fs::write(&message_path, [0, 159, 146, 150]).unwrap();
+
+        let args = EncodeArgs {
+            message: None,
+            message_file: Some(message_path.to_str().unwrap().to_string()),
+            ..encode_args(&file, "")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let png = read_png(&file).unwrap();
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data(),
+            &[0, 159, 146, 150]
+        );
+    }
+
+    #[test]
+    fn test_encode_multiple_message_sources() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            message_stdin: true,
+            ..encode_args(&file, "hello")
+        };
+
+        assert!(encode(&args, &mut Vec::new(), &Logger::default()).is_err());
+    }
+
+    #[test]
+    fn test_encode_spec() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let spec_path = dir.path().join("spec.json");
+        fs::write(
+            &spec_path,
+            r#"[
+                { "type": "tEXt", "message": "Comment" },
+                { "type": "ruSt", "message": "AAEC", "base64": true }
+            ]"#,
+        )
+        .unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: None,
+            message: None,
+            spec: Some(spec_path.to_str().unwrap().to_string()),
+            ..encode_args(&file, "")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"Comment");
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_encode_invalid_spec_entry() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let spec_path = dir.path().join("spec.json");
+        fs::write(
+            &spec_path,
+            r#"[
+                { "type": "tEXt", "message": "Comment" },
+                { "type": "ru5t", "message": "bad type" }
+            ]"#,
+        )
+        .unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: None,
+            message: None,
+            spec: Some(spec_path.to_str().unwrap().to_string()),
+            ..encode_args(&file, "")
+        };
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err.to_string().contains("spec entry 1"));
+
+        let png = read_png(&file).unwrap();
+        assert!(png.chunk_by_type("tEXt").is_none());
+    }
+
+    #[test]
+    fn test_encode_auto_type_with_seed_is_repeatable() {
+        let mut chosen = Vec::new();
+        for _ in 0..2 {
+            let dir = TempDir::new().unwrap();
+            let file = testing_file(&dir);
+            let args = EncodeArgs {
+                chunk_type: None,
+                auto_type: true,
+                seed: Some(42),
+                ..encode_args(&file, "hello")
+            };
+
+            let mut out = Vec::new();
+            encode(&args, &mut out, &Logger::default()).unwrap();
+            chosen.push(String::from_utf8(out).unwrap());
+        }
+
+        assert!(chosen[0].starts_with("chunk type: "));
+        assert!(chosen[0].ends_with("file will grow by 17 bytes\n"));
+        assert_eq!(chosen[0], chosen[1]);
+    }
+
+    #[test]
+    fn test_auto_chunk_type_is_valid_and_unused() {
+        let dir = TempDir::new().unwrap();
+        let mut png = read_png(&testing_file(&dir)).unwrap();
+
+        // The same seed proposes the same types first, so each pick has to
+        // skip the ones already added
+        for _ in 0..5 {
+            let chunk_type = auto_chunk_type(&png, Some(7));
+            assert!(chunk_type.is_valid());
+            assert!(!chunk_type.is_critical());
+            assert!(!chunk_type.is_public());
+            assert!(chunk_type.is_safe_to_copy());
+            assert!(png.chunks().iter().all(|c| c.chunk_type() != &chunk_type));
+
+            png.insert_before_iend(Chunk::new(chunk_type, vec![]).unwrap());
+        }
+        assert_eq!(png.chunks().len(), 8);
+    }
+
+    #[test]
+    fn test_encode_decode_compressed() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            chunk_type: Some("zTXt".to_string()),
+            compress: true,
+            keyword: "Comment".to_string(),
+            ..encode_args(&file, "compressed hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let args = DecodeArgs {
+            chunk_type: "zTXt".to_string(),
+            decompress: true,
+            ..decode_args(&file)
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"compressed hello\n");
+    }
+
+    #[test]
+    fn test_chunk_type_matching_is_case_sensitive() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.encode("RuSt", "hello").unwrap();
+        png.encode("ruSt", "again").unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let mut args = DecodeArgs {
+            filepath: file.clone(),
+            chunk_type: "rust".to_string(),
+            ..Default::default()
+        };
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk type 'rust' not found; did you mean 'ruSt'?"
+        );
+
+        args.ignore_case = true;
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"hello\n");
+
+        let mut args = RemoveArgs {
+            filepath: file.clone(),
+            chunk_types: vec!["rust".to_string()],
+            ..Default::default()
+        };
+        assert!(remove(&args, &mut Vec::new(), &Logger::default()).is_err());
+
+        args.ignore_case = true;
+        let mut out = Vec::new();
+        remove(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "removed 0 rust chunks\nremoved 1 RuSt chunks\nremoved 1 ruSt chunks\n"
+        );
+        assert_eq!(read_png(&file).unwrap().chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_encode_pack_and_decode_unpack() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            message: None,
+            pack: vec![
+                "user=alice".to_string(),
+                "note=".to_string(),
+                "pin=1=2".to_string(),
+            ],
+            ..encode_args(&file, "")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert_eq!(read_png(&file).unwrap().chunks().len(), 4);
+
+        for (name, expected) in [("user", "alice\n"), ("note", "\n"), ("pin", "1=2\n")] {
+            let args = DecodeArgs {
+                unpack: Some(name.to_string()),
+                ..decode_args(&file)
+            };
+            let mut out = Vec::new();
+            decode(&args, &mut out, &Logger::default()).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+
+        let args = DecodeArgs {
+            unpack: Some("missing".to_string()),
+            ..decode_args(&file)
+        };
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "no packed message named 'missing'");
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.insert_before_iend(
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xff, 0x00, 0xfe]).unwrap(),
+        );
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DecodeArgs {
+            filepath: file,
+            chunk_type: "ruSt".to_string(),
+            raw: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, [0xff, 0x00, 0xfe]);
+    }
+
+    #[test]
+    fn test_decode_formats() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.insert_before_iend(
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"Hi!".to_vec()).unwrap(),
+        );
+        png.insert_before_iend(
+            Chunk::new(ChunkType::from_str("biNs").unwrap(), vec![0xff, 0x00]).unwrap(),
+        );
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let decode_as = |chunk_type: &str, format: DecodeFormat| {
+            let args = DecodeArgs {
+                chunk_type: chunk_type.to_string(),
+                format,
+                ..decode_args(&file)
+            };
+            let mut out = Vec::new();
+            decode(&args, &mut out, &Logger::default()).map(|()| out)
+        };
+
+        assert_eq!(decode_as("ruSt", DecodeFormat::Text).unwrap(), b"Hi!\n");
+        assert_eq!(decode_as("ruSt", DecodeFormat::Hex).unwrap(), b"486921\n");
+        assert_eq!(decode_as("ruSt", DecodeFormat::Base64).unwrap(), b"SGkh\n");
+        assert_eq!(decode_as("ruSt", DecodeFormat::Raw).unwrap(), b"Hi!");
+
+        assert!(decode_as("biNs", DecodeFormat::Text).is_err());
+        assert_eq!(decode_as("biNs", DecodeFormat::Hex).unwrap(), b"ff00\n");
+        assert_eq!(decode_as("biNs", DecodeFormat::Base64).unwrap(), b"/wA=\n");
+        assert_eq!(decode_as("biNs", DecodeFormat::Raw).unwrap(), [0xff, 0x00]);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein(b"tEXt", b"tEXt"), 0);
+        assert_eq!(levenshtein(b"tEXT", b"tEXt"), 1);
+        assert_eq!(levenshtein(b"tEX", b"tEXt"), 1);
+        assert_eq!(levenshtein(b"IDAT", b"IEND"), 3);
+        assert_eq!(levenshtein(b"", b"IEND"), 4);
+    }
+
+    #[test]
+    fn test_decode_suggests_near_miss() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.insert_before_iend(Chunk::new(ChunkType::from_str("tEXt").unwrap(), vec![]).unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DecodeArgs {
+            chunk_type: "tEXT".to_string(),
+            ..decode_args(&file)
+        };
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "chunk type 'tEXT' not found; did you mean 'tEXt'?"
+        );
+
+        let args = DecodeArgs {
+            chunk_type: "ruSt".to_string(),
+            ..decode_args(&file)
+        };
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "chunk type 'ruSt' not found");
+    }
+
+    #[test]
+    fn test_encode_decode_verified() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            verified: true,
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let args = DecodeArgs {
+            verified: true,
+            ..decode_args(&file)
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"hello\n");
+
+        // Flip a payload byte and fix up the CRC, as a naive editor would
+        let mut png = read_png(&file).unwrap();
+        let chunk = png
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| c.type_str() == "ruSt")
+            .unwrap();
+        let mut data = chunk.data().to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        chunk.set_data(data).unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err.to_string().contains("has been modified"));
+    }
+
+    #[test]
+    fn test_encode_append_to_existing() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        for message in ["hello", ", world"] {
+            let args = EncodeArgs {
+                append_to_existing: true,
+                ..encode_args(&file, message)
+            };
+            encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        }
+
+        let png = read_png(&file).unwrap();
+        let chunks: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data(), b"hello, world");
+        assert!(chunks[0].crc_ok());
+    }
+
+    #[test]
+    fn test_encode_after_type() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            after: Some("IHDR".to_string()),
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.chunks()[1].type_str(), "ruSt");
+
+        let args = EncodeArgs {
+            after: Some("PLTE".to_string()),
+            ..encode_args(&file, "hello")
+        };
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "no PLTE chunk to insert after");
+    }
+
+    #[test]
+    fn test_encode_decoys() {
+        for seed in 0..4 {
+            let dir = TempDir::new().unwrap();
+            let file = testing_file(&dir);
+            let args = EncodeArgs {
+                verified: true,
+                decoys: 3,
+                seed: Some(seed),
+                ..encode_args(&file, "hello")
+            };
+            encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+            let png = read_png(&file).unwrap();
+            let chunks: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+            assert_eq!(chunks.len(), 4);
+            assert!(chunks.iter().all(|c| c.length() == chunks[0].length()));
+            let genuine = chunks
+                .iter()
+                .filter(|c| integrity::unseal(c.data()).is_ok())
+                .count();
+            assert_eq!(genuine, 1);
+
+            let args = DecodeArgs {
+                verified: true,
+                ..decode_args(&file)
+            };
+            let mut out = Vec::new();
+            decode(&args, &mut out, &Logger::default()).unwrap();
+            assert_eq!(out, b"hello\n");
+        }
+    }
+
+    #[test]
+    fn test_encode_custom_signature() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            signature: Some("deadbeef0d0a1a0a".to_string()),
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let bytes = fs::read(&file).unwrap();
+        assert_eq!(bytes[..8], [0xde, 0xad, 0xbe, 0xef, 0x0d, 0x0a, 0x1a, 0x0a]);
+        assert!(read_png(&file).is_err());
+
+        for bad in ["deadbeef", "zzadbeef0d0a1a0a"] {
+            let args = EncodeArgs {
+                signature: Some(bad.to_string()),
+                ..encode_args(&file, "hello")
+            };
+            assert!(encode(&args, &mut Vec::new(), &Logger::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_encode_replace_keeps_position() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "a longer first message"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let args = EncodeArgs {
+            replace: true,
+            ..encode_args(&file, "short")
+        };
+        let mut out = Vec::new();
+        encode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "file will shrink by 17 bytes\n"
+        );
+
+        let png = read_png(&file).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(png.decode("ruSt").unwrap().as_deref(), Some("short"));
+    }
+
+    #[test]
+    fn test_encode_output_dir() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let output_dir = dir.path().join("encoded").join("nested");
+
+        let args = EncodeArgs {
+            output_dir: Some(output_dir.to_str().unwrap().to_string()),
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let output = output_dir.join("test.stego.png");
+        let png = read_png(output.to_str().unwrap()).unwrap();
+        assert_eq!(png.decode("ruSt").unwrap().as_deref(), Some("hello"));
+        assert!(read_png(&file).unwrap().chunk_by_type("ruSt").is_none());
+
+        // An explicit output still wins
+        let explicit = dir.path().join("explicit.png");
+        let args = EncodeArgs {
+            output: Some(explicit.to_str().unwrap().to_string()),
+            ..args
+        };
+        assert_eq!(encode_output(&args).unwrap(), explicit.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_encode_backup() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let original = fs::read(&file).unwrap();
+
+        let args = EncodeArgs {
+            backup: BackupArgs {
+                suffix: Some(".bak".to_string()),
+                ..Default::default()
+            },
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let backup = format!("{}.bak", file);
+        assert_eq!(fs::read(&backup).unwrap(), original);
+        assert!(read_png(&file).unwrap().chunk_by_type("ruSt").is_some());
+
+        // A second in-place edit refuses to clobber the backup without --force
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("already exists; pass --force to replace it"));
+        assert_eq!(fs::read(&backup).unwrap(), original);
+
+        let edited = fs::read(&file).unwrap();
+        let mut args = args;
+        args.backup.force = true;
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), edited);
+    }
+
+    #[test]
+    fn test_encode_dry_run_leaves_file_unchanged() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let before = fs::read(&file).unwrap();
+
+        let args = EncodeArgs {
+            filepath: file.clone(),
+            chunk_type: Some("ruSt".to_string()),
+            message: Some("hello".to_string()),
+            dry_run: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        encode(&args, &mut out, &Logger::default()).unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), before);
+        let expected = format!(
+            "would add ruSt chunk (5 bytes)\nfile will grow by 17 bytes\n\
+             would write {} bytes to {}\n",
+            before.len() + 17,
+            file
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_encode_split_and_decode_join() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let message = "a message long enough to need three parts";
+
+        let args = EncodeArgs {
+            filepath: file.clone(),
+            chunk_type: Some("ruSt".to_string()),
+            message: Some(message.to_string()),
+            split: Some(24),
+            ..Default::default()
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let mut png = read_png(&file).unwrap();
+        let parts: Vec<usize> = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.type_str() == "ruSt")
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(parts.len(), 3);
+        png.chunks_mut().swap(parts[0], parts[2]);
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = DecodeArgs {
+            filepath: file,
+            chunk_type: "ruSt".to_string(),
+            join: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, format!("{}\n", message).as_bytes());
+    }
+
+    #[test]
+    fn test_decode_unsupported_compression_method() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = EncodeArgs {
+            chunk_type: Some("zTXt".to_string()),
+            compress: true,
+            keyword: "Comment".to_string(),
+            compression_method: 1,
+            ..encode_args(&file, "compressed hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let args = DecodeArgs {
+            chunk_type: "zTXt".to_string(),
+            decompress: true,
+            ..decode_args(&file)
+        };
+        let err = decode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported compression method 1");
+    }
+
+    #[test]
+    fn test_encode_strict_chunk_type() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+
+        let args = EncodeArgs {
+            strict: true,
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: Some("Rust".to_string()),
+            strict: true,
+            ..encode_args(&file, "hello")
+        };
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err.to_string().contains("invalid reserved bit"));
+        assert!(read_png(&file).unwrap().chunk_by_type("Rust").is_none());
+
+        // Without --strict the chunk is still written
+        let args = EncodeArgs {
+            chunk_type: Some("Rust".to_string()),
+            ..encode_args(&file, "hello")
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert!(read_png(&file).unwrap().chunk_by_type("Rust").is_some());
+    }
+
+    #[test]
+    fn test_encode_critical_chunk_type() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+
+        let args = EncodeArgs {
+            chunk_type: Some("IDAT".to_string()),
+            strict: true,
+            ..encode_args(&file, "hello")
+        };
+        let err = encode(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("chunk type 'IDAT' is critical (the first letter is uppercase)"));
+        assert!(err.to_string().contains("such as 'idAt' instead"));
+        assert_eq!(read_png(&file).unwrap().chunks().len(), 3);
+
+        let err = check_chunk_type(
+            &ChunkType::from_str("Rust").unwrap(),
+            true,
+            &Logger::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid reserved bit"));
+        assert!(err.to_string().contains("such as 'ruSt' instead"));
+    }
+
+    #[test]
+    fn test_encode_plan() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let original = fs::read(&file).unwrap();
+        let spec_path = dir.path().join("spec.json");
+        fs::write(
+            &spec_path,
+            r#"[
+                { "type": "tEXt", "message": "Comment" },
+                { "type": "ruSt", "message": "AAEC", "base64": true }
+            ]"#,
+        )
+        .unwrap();
+
+        let args = EncodeArgs {
+            chunk_type: None,
+            message: None,
+            spec: Some(spec_path.to_str().unwrap().to_string()),
+            output: Some("out.png".to_string()),
+            plan: true,
+            ..encode_args(&file, "")
+        };
+        let mut out = Vec::new();
+        encode(&args, &mut out, &Logger::default()).unwrap();
+
+        let expected = format!(
+            "1. read {}\n2. add tEXt chunk (7 bytes)\n3. add ruSt chunk (3 bytes)\n4. write to out.png\n",
+            file
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+        assert_eq!(fs::read(&file).unwrap(), original);
+        assert!(!std::path::Path::new("out.png").exists());
+    }
+
+    #[test]
+    fn test_encode_plan_follows_options() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hi"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        let original = fs::read(&file).unwrap();
+        let plan = |args: EncodeArgs| {
+            let mut out = Vec::new();
+            encode(&args, &mut out, &Logger::default()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let args = EncodeArgs {
+            replace: true,
+            drop_trailing: true,
+            signature: Some("0001020304050607".to_string()),
+            plan: true,
+            ..encode_args(&file, "hello")
+        };
+        let expected = format!(
+            "1. read {}\n2. replace ruSt chunk (5 bytes)\n3. drop 0 trailing bytes\n\
+             4. write signature 0001020304050607\n5. write to {}\n",
+            file, file
+        );
+        assert_eq!(plan(args), expected);
+
+        let args = EncodeArgs {
+            split: Some(12),
+            plan: true,
+            ..encode_args(&file, "hello")
+        };
+        let expected = format!(
+            "1. read {}\n2. add ruSt chunk (12 bytes)\n3. add ruSt chunk (9 bytes)\n\
+             4. write to {}\n",
+            file, file
+        );
+        assert_eq!(plan(args), expected);
+
+        let args = EncodeArgs {
+            after: Some("IHDR".to_string()),
+            plan: true,
+            ..encode_args(&file, "hello")
+        };
+        let expected = format!(
+            "1. read {}\n2. add ruSt chunk (5 bytes) after IHDR\n3. write to {}\n",
+            file, file
+        );
+        assert_eq!(plan(args), expected);
+
+        let args = EncodeArgs {
+            plan: true,
+            ..encode_args(&file, "hi")
+        };
+        let expected = format!(
+            "1. read {}\n2. leave the message, which is already there\n",
+            file
+        );
+        assert_eq!(plan(args), expected);
+        assert_eq!(fs::read(&file).unwrap(), original);
+    }
+
+    #[test]
+    fn test_typeinfo() {
+        let args = TypeinfoArgs {
+            chunk_type: "ruSt".to_string(),
+        };
+        let mut out = Vec::new();
+        typeinfo(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("bytes:        114 117 83 116"));
+        assert!(out.contains("hex:          72 75 53 74"));
+        assert!(out.contains("ancillary"));
+        assert!(out.contains("private"));
+        assert!(out.contains("reserved bit: valid"));
+        assert!(out.contains("copying:      safe to copy"));
+        assert!(!out.contains("description"));
+    }
+
+    #[test]
+    fn test_chunk_types() {
+        let mut out = Vec::new();
+        chunk_types(&ChunkTypesArgs {}, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(out.lines().count(), STANDARD_TYPES.len());
+        assert!(out.starts_with("IHDR  critical   Image header\n"));
+        assert!(out.contains("\ntEXt  ancillary  Textual data\n"));
+        assert!(out.contains("\niTXt  ancillary  International textual data\n"));
+    }
+
+    #[test]
+    fn test_quickstart() {
+        let args = QuickstartArgs {
+            message: "hi".to_string(),
+            seed: Some(7),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        quickstart(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let suggested = out.lines().next().unwrap();
+        let suggested = &suggested["suggested chunk type: ".len()..][..4];
+        let chunk_type = ChunkType::from_str(suggested).unwrap();
+        assert!(chunk_type.is_valid());
+        assert!(!chunk_type.is_critical() && !chunk_type.is_public());
+        assert!(chunk_type.is_safe_to_copy());
+        assert!(out.contains(&format!("  pngme encode FILE {} hi\n", suggested)));
+
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = QuickstartArgs {
+            filepath: Some(file.clone()),
+            ..args
+        };
+        quickstart(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.decode(suggested).unwrap().as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("hi"), "hi");
+        assert_eq!(shell_quote("dir/a-b.png"), "dir/a-b.png");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("$HOME `id`\n"), "'$HOME `id`\n'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_count() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "one"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        encode(
+            &encode_args(&file, "three"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let args = CountArgs {
+            filepath: file,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        count(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[1], "IDAT          1           15");
+        assert_eq!(lines[4], "ruSt          2           32");
+        assert_eq!(lines[5], "total         5           84");
+    }
+
+    #[test]
+    fn test_extract() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let payload = STANDARD.encode([0, 255, 0, 128]);
+        let args = EncodeArgs {
+            base64: true,
+            ..encode_args(&file, &payload)
+        };
+        encode(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        encode(
+            &encode_args(&file, "second"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let output = dir.path().join("extracted.bin");
+        let mut args = ExtractArgs {
+            filepath: file.clone(),
+            chunk_type: "ruSt".to_string(),
+            output: output.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        extract(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), [0, 255, 0, 128]);
+
+        args.index = 1;
+        extract(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"second");
+
+        args.index = 2;
+        assert!(extract(&args, &mut Vec::new(), &Logger::default()).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = VerifyArgs {
+            filepaths: vec![file.clone()],
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        verify(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().filter(|l| l.starts_with("ok ")).count(), 3);
+
+        let mut png = read_png(&file).unwrap();
+        let idat = png.remove_chunk("IDAT").unwrap();
+        let iend = png.remove_chunk("IEND").unwrap();
+        png.append_chunk(idat.with_corrupt_crc());
+        png.append_chunk(iend);
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        let err = verify(&args, &mut out, &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 3 chunks failed CRC verification");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().nth(1).unwrap().starts_with("FAIL IDAT stored "));
+    }
+
+    #[test]
+    fn test_fix() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let chunks: Vec<Chunk> = read_png(&file)
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec()).unwrap())
+            .enumerate()
+            .map(|(i, c)| if i < 2 { c.with_corrupt_crc() } else { c })
+            .collect();
+        fs::write(&file, Png::from_chunks(chunks).as_bytes()).unwrap();
+        assert!(read_png(&file).is_err());
+
+        let output = dir.path().join("fixed.png");
+        let args = FixArgs {
+            filepath: file,
+            output: output.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        fix(&args, &mut out, &Logger::default()).unwrap();
+
+        assert_eq!(out, b"fixed 2 of 3 chunk CRCs\n");
+        assert!(read_png(output.to_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_fix_recover() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let original = fs::read(&file).unwrap();
+        // Garbage between IHDR, which ends at 0x21, and IDAT
+        let mut bytes = original[..0x21].to_vec();
+        bytes.extend(b"\xff\xff\xff\xffjunk");
+        bytes.extend(&original[0x21..]);
+        fs::write(&file, &bytes).unwrap();
+
+        let output = dir.path().join("fixed.png");
+        let args = FixArgs {
+            filepath: file,
+            output: output.to_str().unwrap().to_string(),
+            recover: false,
+        };
+        assert!(fix(&args, &mut Vec::new(), &Logger::default()).is_err());
+
+        let args = FixArgs {
+            recover: true,
+            ..args
+        };
+        fix(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), original);
+    }
+
+    #[test]
+    fn test_normalize_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("messy.png");
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]).unwrap(),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3]).unwrap(),
+            Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 0xB1, 0x8F])
+                .unwrap()
+                .with_corrupt_crc(),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap(),
+        ];
+        let mut bytes = Png::from_chunks(chunks).as_bytes();
+        bytes.extend_from_slice(b"trailing");
+        fs::write(&file, &bytes).unwrap();
+
+        let once = dir.path().join("once.png");
+        let twice = dir.path().join("twice.png");
+        let normalize_to = |input: &Path, output: &Path| {
+            let args = NormalizeArgs {
+                filepath: input.to_str().unwrap().to_string(),
+                output: Some(output.to_str().unwrap().to_string()),
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            normalize(&args, &mut out, &Logger::default()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = normalize_to(&file, &once);
+        assert_eq!(out, "fixed 1 chunk CRCs, dropped 8 trailing bytes\n");
+        let out = normalize_to(&once, &twice);
+        assert_eq!(out, "fixed 0 chunk CRCs, dropped 0 trailing bytes\n");
+
+        let normalized = fs::read(&once).unwrap();
+        assert_eq!(normalized, fs::read(&twice).unwrap());
+        let png = Png::from_bytes(&normalized).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "IEND"]);
+        assert!(png.trailing_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_orders_text_chunks() {
+        let dir = TempDir::new().unwrap();
+        let text = |data: &[u8]| Chunk::new(ChunkType::from_str("tEXt").unwrap(), data.to_vec());
+        let normalized = [[b"A\0one", b"B\0two"], [b"B\0two", b"A\0one"]].map(|texts| {
+            let file = testing_file(&dir);
+            let mut png = read_png(&file).unwrap();
+            for data in texts {
+                png.insert_before_iend(text(data).unwrap());
+            }
+            fs::write(&file, png.as_bytes()).unwrap();
+
+            let args = NormalizeArgs {
+                filepath: file.clone(),
+                ..Default::default()
+            };
+            normalize(&args, &mut Vec::new(), &Logger::default()).unwrap();
+            fs::read(&file).unwrap()
+        });
+
+        assert_eq!(normalized[0], normalized[1]);
+        let png = Png::from_bytes(&normalized[0]).unwrap();
+        assert_eq!(png.chunks()[2].data(), b"A\0one");
+    }
+
+    #[test]
+    fn test_hash_normalize_ignores_ancillary_order() {
+        let dir = TempDir::new().unwrap();
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).unwrap()
+        };
+        let gama = || chunk("gAMA", &[0, 0, 0xB1, 0x8F]);
+        let text_a = || chunk("tEXt", b"A\0one");
+        let text_b = || chunk("tEXt", b"B\0two");
+        let mut index = 0;
+        let files = [
+            vec![
+                chunk("IHDR", &[0; 13]),
+                gama(),
+                chunk("IDAT", &[1]),
+                chunk("IEND", &[]),
+            ],
+            vec![
+                chunk("IHDR", &[0; 13]),
+                chunk("IDAT", &[1]),
+                gama(),
+                chunk("IEND", &[]),
+            ],
+            vec![
+                chunk("IHDR", &[0; 13]),
+                text_a(),
+                text_b(),
+                chunk("IDAT", &[1]),
+                chunk("IEND", &[]),
+            ],
+            vec![
+                chunk("IHDR", &[0; 13]),
+                text_b(),
+                text_a(),
+                chunk("IDAT", &[1]),
+                chunk("IEND", &[]),
+            ],
+            vec![
+                chunk("IHDR", &[0; 13]),
+                chunk("IDAT", &[1]),
+                text_b(),
+                text_a(),
+                chunk("IEND", &[]),
+            ],
+        ]
+        .map(|chunks| {
+            index += 1;
+            let path = dir.path().join(format!("{}.png", index));
+            fs::write(&path, Png::from_chunks(chunks).as_bytes()).unwrap();
+            path.to_str().unwrap().to_string()
+        });
+
+        let hash_of = |filepath: &str, normalize: bool| {
+            let args = HashArgs {
+                filepath: filepath.to_string(),
+                normalize,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            hash(&args, &mut out, &Logger::default()).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            out.split_whitespace().next().unwrap().to_string()
+        };
+
+        assert_eq!(hash_of(&files[0], false).len(), 64);
+        assert_ne!(hash_of(&files[0], false), hash_of(&files[1], false));
+        assert_eq!(hash_of(&files[0], true), hash_of(&files[1], true));
+        assert_eq!(hash_of(&files[0], true), hash_of(&files[0], false));
+
+        // Text chunks may go anywhere, so neither their order nor which side
+        // of IDAT they are on changes the hash
+        assert_ne!(hash_of(&files[2], false), hash_of(&files[3], false));
+        assert_ne!(hash_of(&files[2], false), hash_of(&files[4], false));
+        assert_eq!(hash_of(&files[2], true), hash_of(&files[3], true));
+        assert_eq!(hash_of(&files[2], true), hash_of(&files[4], true));
+    }
+
+    #[test]
+    fn test_hash_crc32() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = HashArgs {
+            filepath: file.clone(),
+            algorithm: HashAlgorithm::Crc32,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        hash(&args, &mut out, &Logger::default()).unwrap();
+
+        let expected = PNG_CRC.checksum(&fs::read(&file).unwrap());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{:08x}  {}\n", expected, file)
+        );
+    }
+
+    #[test]
+    fn test_relabel() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "moved"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let args = RelabelArgs {
+            filepath: file.clone(),
+            old_type: "ruSt".to_string(),
+            new_type: "myTx".to_string(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        relabel(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"relabelled 1 ruSt chunks as myTx\n");
+
+        let png = read_png(&file).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert_eq!(png.chunk_by_type("myTx").unwrap().data(), b"moved");
+        assert!(relabel(&args, &mut Vec::new(), &Logger::default()).is_err());
+    }
+
+    #[test]
+    fn test_read_chunk_stream_input() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        let bytes = fs::read(&file).unwrap();
+        let stream = dir.path().join("test.chunks");
+        fs::write(&stream, &bytes[8..]).unwrap();
+        let stream = stream.to_str().unwrap().to_string();
+
+        let args = DecodeArgs {
+            input: InputFormatArgs {
+                format: InputFormat::Chunks,
+            },
+            ..decode_args(&stream)
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"hello\n");
+
+        let args = CountArgs {
+            filepath: stream.clone(),
+            input: InputFormatArgs {
+                format: InputFormat::Chunks,
+            },
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        count(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("total         4"));
+
+        let err = decode(&decode_args(&stream), &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&crate::PngError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_archive_unarchive_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let archive_path = dir.path().join("test.pngchunks");
+        let archive_path = archive_path.to_str().unwrap().to_string();
+        let args = ArchiveArgs {
+            filepath: file.clone(),
+            output: archive_path.clone(),
+            ..Default::default()
+        };
+        archive(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let original = fs::read(&file).unwrap();
+        assert_eq!(fs::read(&archive_path).unwrap(), original[8..]);
+
+        let output = dir.path().join("rebuilt.png");
+        let args = UnarchiveArgs {
+            archive: archive_path,
+            output: output.to_str().unwrap().to_string(),
+        };
+        unarchive(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        assert_eq!(fs::read(&output).unwrap(), original);
+    }
+
+    #[test]
+    fn test_print_at_offset() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut bytes = vec![0xAB; 100];
+        bytes.extend(fs::read(&file).unwrap());
+        fs::write(&file, bytes).unwrap();
+
+        let mut args = PrintArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        assert!(print(&args, &mut Vec::new(), &Logger::default()).is_err());
+
+        args.offset = 100;
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("PNG header"));
+
+        args.offset = 10_000;
+        assert!(print(&args, &mut Vec::new(), &Logger::default()).is_err());
+    }
+
+    #[test]
+    fn test_check_message_length() {
+        assert!(check_message_length(0).is_ok());
+        assert!(check_message_length(Chunk::MAX_LENGTH as u64).is_ok());
+
+        let err = check_message_length(1 << 32).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "message is 4294967296 bytes, more than the 2147483647 a chunk can hold"
+        );
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"Hello, hex dump!\x00\x01\xffend");
+        assert_eq!(
+            dump,
+            "00000000  48 65 6c 6c 6f 2c 20 68 65 78 20 64 75 6d 70 21  Hello, hex dump!\n\
+             00000010  00 01 ff 65 6e 64                                ...end\n"
+        );
+        assert_eq!(hex_dump(&[]), "");
+    }
+
+    #[test]
+    fn test_print_significant_bits() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        let sbit = Chunk::new(ChunkType::from_str("sBIT").unwrap(), vec![5]).unwrap();
+        png.insert_chunk_at(1, sbit).unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let mut args = PrintArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\nSignificant bits: 5\n"));
+
+        args.only_critical = true;
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("Significant bits"));
+    }
+
+    #[test]
+    fn test_print_hex() {
+        let dir = TempDir::new().unwrap();
+        let args = PrintArgs {
+            filepaths: vec![testing_file(&dir)],
+            hex: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "IHDR (13 bytes)");
+        assert!(lines[1].starts_with("00000000  00 00 00"));
+        assert_eq!(lines[2], "IDAT (3 bytes)");
+        assert_eq!(
+            lines[3],
+            format!("00000000  01 02 03{}  ...", " ".repeat(39))
+        );
+        assert_eq!(lines[4], "IEND (0 bytes)");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn test_encode_preserves_trailing_bytes() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut bytes = fs::read(&file).unwrap();
+        bytes.extend_from_slice(b"after the end");
+        fs::write(&file, bytes).unwrap();
+
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        let png = read_png(&file).unwrap();
+        assert_eq!(png.decode("ruSt").unwrap().as_deref(), Some("hello"));
+        assert_eq!(png.trailing_bytes(), b"after the end");
+
+        let args = RemoveArgs {
+            filepath: file.clone(),
+            chunk_types: vec!["ruSt".to_string()],
+            drop_trailing: true,
+            ..Default::default()
+        };
+        remove(&args, &mut Vec::new(), &Logger::default()).unwrap();
+        assert!(read_png(&file).unwrap().trailing_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_show_trailing() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut bytes = fs::read(&file).unwrap();
+        bytes.extend_from_slice(b"0123456789");
+        fs::write(&file, bytes).unwrap();
+
+        let args = PrintArgs {
+            filepaths: vec![file.clone()],
+            show_trailing: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        let expected = format!(
+            "10 bytes after IEND:\n00000000  30 31 32 33 34 35 36 37 38 39{}  0123456789\n",
+            " ".repeat(18)
+        );
+        assert!(String::from_utf8(out).unwrap().ends_with(&expected));
+
+        let args = InfoArgs {
+            filepath: file,
+            show_trailing: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        info(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(&expected));
+    }
+
+    #[test]
+    fn test_print_image_header() {
+        let dir = TempDir::new().unwrap();
+        let args = PrintArgs {
+            filepaths: vec![testing_file(&dir)],
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(
+            "Image: 0x0, bit depth 0, color type 0, compression 0, filter 0, interlace 0\n"
+        ));
+    }
+
+    #[test]
+    fn test_diff() {
+        let dir = TempDir::new().unwrap();
+        let left = testing_file(&dir);
+        let right = dir.path().join("right.png");
+        let right = right.to_str().unwrap().to_string();
+
+        let mut png = read_png(&left).unwrap();
+        png.chunks_mut()[1].set_data(vec![1, 2, 3, 4, 5]).unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 4]).unwrap(),
+        )
+        .unwrap();
+        png.remove_chunk("IEND").unwrap();
+        fs::write(&right, png.as_bytes()).unwrap();
+
+        let args = DiffArgs {
+            left: left.clone(),
+            right: right.clone(),
+        };
+        let mut out = Vec::new();
+        let err = diff(&args, &mut out, &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "3 chunks differ");
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("+ gAMA          4 "));
+        assert!(lines[1].starts_with("~ IDAT          5 "));
+        assert!(lines[1].ends_with("(+2 bytes)"));
+        assert!(lines[2].starts_with("- IEND          0 "));
+
+        let args = DiffArgs {
+            left: left.clone(),
+            right: left,
+        };
+        let mut out = Vec::new();
+        diff(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"files have identical chunks\n");
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = TempDir::new().unwrap();
+        let destination = testing_file(&dir);
+        let source = dir.path().join("source.png");
+        let mut png = read_png(&destination).unwrap();
+        for (chunk_type, data) in [("tEXt", "Author\0Ann"), ("gAMA", "\0\0\0\0")] {
+            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+            png.insert_before_iend(Chunk::new(chunk_type, data.as_bytes().to_vec()).unwrap());
+        }
+        fs::write(&source, png.as_bytes()).unwrap();
+
+        let output = dir.path().join("merged.png");
+        let output = output.to_str().unwrap().to_string();
+        let merge_args = MergeArgs {
+            source: source.to_str().unwrap().to_string(),
+            destination: destination.clone(),
+            output: Some(output.clone()),
+            types: vec!["tEXt".to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        merge(&merge_args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"copied 1 chunks (22 bytes)\n");
+
+        let merged = read_png(&output).unwrap();
+        let types: Vec<String> = merged.chunks().iter().map(Chunk::type_str).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "tEXt", "IEND"]);
+        let args = DecodeArgs {
+            chunk_type: "tEXt".to_string(),
+            format: DecodeFormat::Raw,
+            ..decode_args(&output)
+        };
+        let mut out = Vec::new();
+        decode(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"Author\0Ann");
+
+        let args = MergeArgs {
+            output: None,
+            types: vec!["tEXt".to_string(), "IDAT".to_string()],
+            ..merge_args
+        };
+        let err = merge(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "IDAT is a critical chunk type and can't be merged"
+        );
+        assert_eq!(read_png(&destination).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_strip() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+        let mut png = read_png(&file).unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 4]).unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let output = dir.path().join("stripped.png");
+        let args = StripArgs {
+            filepath: file.clone(),
+            output: Some(output.to_str().unwrap().to_string()),
+            keep: vec!["gAMA".to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        strip(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"removed 1 chunks (17 bytes)\n");
+
+        let stripped = read_png(output.to_str().unwrap()).unwrap();
+        let types: Vec<String> = stripped
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "IEND"]);
+        assert_eq!(read_png(&file).unwrap().chunks().len(), 5);
+    }
+
+    #[test]
+    fn test_info() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.chunks_mut()[0]
+            .set_data(vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0])
+            .unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(
+                ChunkType::from_str("tEXt").unwrap(),
+                b"Title\0dice".to_vec(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let mut out = Vec::new();
+        let args = InfoArgs {
+            filepath: file.clone(),
+            ..Default::default()
+        };
+        info(&args, &mut out, &Logger::default()).unwrap();
+
+        let expected = format!(
+            "{}\n  dimensions:   3x2\n  bit depth:    8\n  color type:   RGB\n  \
+             interlace:    none\n  chunks:       4\n  text chunks:  1\n    Title: dice\n  \
+             ancillary:    1\n  types:\n    \
+             IHDR (critical, public, reserved-valid, unsafe-to-copy)\n    \
+             tEXt (ancillary, public, reserved-valid, safe-to-copy)\n    \
+             IDAT (critical, public, reserved-valid, unsafe-to-copy)\n    \
+             IEND (critical, public, reserved-valid, unsafe-to-copy)\n",
+            file
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_info_and_print_international_text() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(
+                ChunkType::from_str("iTXt").unwrap(),
+                b"Title\0\0\0fr\0Titre\0\xc3\xa9t\xc3\xa9".to_vec(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = InfoArgs {
+            filepath: file.clone(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        info(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("  text chunks:  1\n    Title [fr]: été\n"));
+
+        let args = PrintArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("Text: Title: été\n"));
+    }
+
+    #[test]
+    fn test_info_without_ihdr() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("bare.png");
+        let chunks = vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap()];
+        fs::write(&file, Png::from_chunks(chunks).as_bytes()).unwrap();
+
+        let args = InfoArgs {
+            filepath: file.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        info(&args, &mut out, &Logger::default()).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("dimensions"));
+        assert!(out.contains("  chunks:       1\n"));
+    }
+
+    #[test]
+    fn test_list_color() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.chunks_mut()[1] = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3])
+            .unwrap()
+            .with_corrupt_crc();
+        png.insert_before_iend(Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![]).unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = ListArgs {
+            filepaths: vec![file],
+            color: ColorChoice::Always,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("\x1b[36mIHDR\x1b[0m         13 "));
+        assert!(lines[1].contains(" \x1b[31m"));
+        assert!(lines[2].starts_with("\x1b[33mruSt\x1b[0m"));
+        assert!(!lines[2].contains("\x1b[31m"));
+
+        let args = ListArgs {
+            color: ColorChoice::Never,
+            ..args
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        assert!(!out.contains(&0x1b));
+    }
+
+    #[test]
+    fn test_info_and_list_time() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.insert_before_iend(
+            Chunk::new(
+                ChunkType::from_str("tIME").unwrap(),
+                vec![0x07, 0xD0, 1, 1, 0, 0, 0],
+            )
+            .unwrap(),
+        );
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = InfoArgs {
+            filepath: file.clone(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        info(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("  modified:     2000-01-01T00:00:00Z\n"));
+
+        let args = ListArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out
+            .lines()
+            .nth(2)
+            .unwrap()
+            .ends_with("  2000-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(&[]), 0.0);
+        assert_eq!(shannon_entropy(&[0; 4096]), 0.0);
+        assert_eq!(shannon_entropy(&[0, 1, 2, 3]), 2.0);
+
+        let mut rng = Rng::new(1);
+        let random: Vec<u8> = (0..65536).map(|_| rng.next_u64() as u8).collect();
+        assert!(shannon_entropy(&random) > 7.9);
+    }
+
+    #[test]
+    fn test_entropy_flags_random_chunks() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        let mut rng = Rng::new(1);
+        let random: Vec<u8> = (0..4096).map(|_| rng.next_u64() as u8).collect();
+        png.insert_before_iend(Chunk::new(ChunkType::from_str("ruSt").unwrap(), random).unwrap());
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = EntropyArgs {
+            filepath: file,
+            threshold: 7.5,
+        };
+        let mut out = Vec::new();
+        entropy(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "IHDR         13 0.000");
+        assert!(lines[2].starts_with("ruSt       4096 7.9"));
+        assert!(lines[2].ends_with("  likely compressed or encrypted"));
+        assert_eq!(lines[3], "IEND          0 0.000");
+    }
+
+    #[test]
+    fn test_scan_finds_embedded_png() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let inner = fs::read(&file).unwrap();
+        let mut outer = read_png(&file).unwrap();
+        let mut data = b"junk".to_vec();
+        data.extend(&inner);
+        outer.insert_before_iend(Chunk::new(ChunkType::from_str("priv").unwrap(), data).unwrap());
+        fs::write(&file, outer.as_bytes()).unwrap();
+
+        let args = ScanArgs {
+            filepath: file.clone(),
+            max_depth: 3,
+        };
+        let mut out = Vec::new();
+        scan(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "IHDR 13\nIDAT 3\npriv 64\n  embedded PNG at byte 4:\n  IHDR 13\n  IDAT 3\n  \
+             IEND 0\nIEND 0\nfound 1 embedded PNGs\n"
+        );
+
+        let args = ScanArgs {
+            filepath: file,
+            max_depth: 1,
+        };
+        let mut out = Vec::new();
+        scan(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("IEND 0\nfound 0 embedded PNGs\n"));
+    }
+
+    #[test]
+    fn test_verify_check_only() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        png.chunks_mut()[1] = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3])
+            .unwrap()
+            .with_corrupt_crc();
+        let corrupt = dir.path().join("corrupt.png");
+        fs::write(&corrupt, png.as_bytes()).unwrap();
+        let corrupt = corrupt.to_str().unwrap().to_string();
+
+        let args = VerifyArgs {
+            filepaths: vec![file.clone(), corrupt.clone()],
+            check_only: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let err = verify(&args, &mut out, &Logger::default()).unwrap_err();
+        assert_eq!(err.downcast_ref::<CheckFailed>().unwrap().exit_code, 1);
+        assert_eq!(err.to_string(), "1 passed, 1 failed, 0 unreadable");
+        let crc = Chunk::crc_of(&ChunkType::from_str("IDAT").unwrap(), &[1, 2, 3]);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "pass {}\nfail {}: IDAT stored {:08x}, expected {:08x}\n\
+                 summary: 1 passed, 1 failed, 0 unreadable\n",
+                file, corrupt, !crc, crc
+            )
+        );
+
+        let args = VerifyArgs {
+            filepaths: vec![file.clone()],
+            ..args
+        };
+        let mut out = Vec::new();
+        verify(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("pass {}\nsummary: 1 passed, 0 failed, 0 unreadable\n", file)
+        );
+    }
+
+    #[test]
+    fn test_list_and_verify_several_files() {
+        let dir = TempDir::new().unwrap();
+        let first = testing_file(&dir);
+        let second = dir.path().join("second.png");
+        let mut png = read_png(&first).unwrap();
+        png.chunks_mut()[1] = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3])
+            .unwrap()
+            .with_corrupt_crc();
+        fs::write(&second, png.as_bytes()).unwrap();
+        let second = second.to_str().unwrap().to_string();
+
+        let args = ListArgs {
+            filepaths: vec![first.clone(), second.clone()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], format!("==> {} <==", first));
+        assert_eq!(lines[4], "");
+        assert_eq!(lines[5], format!("==> {} <==", second));
+
+        let mut args = ListArgs { jobs: 4, ..args };
+        let mut parallel = Vec::new();
+        list(&args, &mut parallel, &Logger::default()).unwrap();
+        assert_eq!(String::from_utf8(parallel).unwrap(), out);
+
+        let mut args = VerifyArgs {
+            filepaths: std::mem::take(&mut args.filepaths),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let err = verify(&args, &mut out, &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 files failed verification");
+        assert_eq!(
+            String::from_utf8(out.clone())
+                .unwrap()
+                .matches("==> ")
+                .count(),
+            2
+        );
+
+        args.jobs = 2;
+        let mut parallel = Vec::new();
+        let err = verify(&args, &mut parallel, &Logger::default()).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 files failed verification");
+        assert_eq!(parallel, out);
+    }
+
+    #[test]
+    fn test_for_each_file_ordered_keeps_input_order() {
+        let paths: Vec<String> = (0..6).map(|i| i.to_string()).collect();
+        // Earlier files take longer, so they finish last
+        let slow_first = |path: &str, out: &mut Vec<u8>| {
+            let i: u64 = path.parse()?;
+            thread::sleep(std::time::Duration::from_millis((6 - i) * 20));
+            if i == 3 {
+                Err("boom")?;
+            }
+            out.extend_from_slice(path.as_bytes());
+            Ok(())
+        };
+
+        let mut emitted = Vec::new();
+        for_each_file_ordered(&paths, 6, slow_first, |i, output, result| {
+            emitted.push((i, output.to_vec(), result.map_err(|e| e.to_string())));
+            Ok(())
+        })
+        .unwrap();
+        let order: Vec<usize> = emitted.iter().map(|(i, ..)| *i).collect();
+        assert_eq!(order, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(emitted[2], (2, b"2".to_vec(), Ok(())));
+        assert_eq!(emitted[3], (3, vec![], Err("boom".to_string())));
+
+        let mut emitted = 0;
+        let err = for_each_file_ordered(&paths, 2, slow_first, |_, _, result| {
+            emitted += 1;
+            result
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+        assert_eq!(emitted, 4);
+    }
+
+    #[test]
+    fn test_list() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let args = ListArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 3);
+        assert!(out.starts_with("IHDR         13 "));
+        assert!(out
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("  IHDR (critical, public, reserved-valid, unsafe-to-copy)"));
+    }
+
+    #[test]
+    fn test_list_and_print_text_chunks() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        let data = b"Software\0pngme \xe9dition".to_vec();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("tEXt").unwrap(), data).unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = ListArgs {
+            filepaths: vec![file.clone()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let line = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .to_string();
+        assert!(line.starts_with("tEXt         22 "));
+        assert!(line.ends_with("  Software: pngme édition"));
+
+        let args = PrintArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("Text: Software: pngme édition\n"));
+    }
+
+    #[test]
+    fn test_list_type_filter() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let args = ListArgs {
+            filepaths: vec![file.clone()],
+            type_filter: Some("^[a-z]".to_string()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.starts_with("ruSt "));
+
+        let args = ListArgs {
+            type_filter: Some("^[A-Z".to_string()),
+            ..args
+        };
+        let err = list(&args, &mut Vec::new(), &Logger::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid --type-filter '^[A-Z': "));
+    }
+
+    #[test]
+    fn test_list_only_ancillary() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        let data = b"Comment\0hello".to_vec();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("tEXt").unwrap(), data).unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = ListArgs {
+            filepaths: vec![file.clone()],
+            only_ancillary: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.starts_with("tEXt         13 "));
+
+        let args = PrintArgs {
+            filepaths: vec![file],
+            only_critical: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().filter(|l| l.starts_with("13IHDR")).count(), 1);
+        assert!(!out.contains("Text:"));
+    }
+
+    #[test]
+    fn test_print_compressed_text_chunk() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        let mut png = read_png(&file).unwrap();
+        let data = text::compress_text("Comment", b"squeezed", 0).unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("zTXt").unwrap(), data).unwrap(),
+        )
+        .unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = PrintArgs {
+            filepaths: vec![file],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("Text: Comment: squeezed\n"));
+    }
+
+    #[test]
+    fn test_list_and_print_json() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+
+        let args = ListArgs {
+            filepaths: vec![file.clone()],
+            json: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let listed: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 3);
+        assert_eq!(listed[1]["type"], "IDAT");
+        assert_eq!(listed[1]["length"], 3);
+        assert_eq!(listed[1]["critical"], true);
+        assert!(listed[1].get("data").is_none());
+
+        let idat = read_png(&file).unwrap().chunks()[1].crc();
+        assert_eq!(listed[1]["crc"], format!("{:08x}", idat));
+
+        let args = PrintArgs {
+            filepaths: vec![file],
+            json: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        print(&args, &mut out, &Logger::default()).unwrap();
+        let printed: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(printed[1]["data"], "AQID");
+
+        // Keys come out in a fixed order
+        let text = String::from_utf8(out).unwrap();
+        let crc = text.find("\"crc\"").unwrap();
+        let type_key = text.find("\"type\"").unwrap();
+        assert!(crc < type_key);
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        encode(
+            &encode_args(&file, "hello"),
+            &mut Vec::new(),
+            &Logger::default(),
+        )
+        .unwrap();
+
+        let args = RemoveArgs {
+            filepath: file.clone(),
+            chunk_types: vec!["ruSt".to_string()],
+            ..Default::default()
+        };
+        remove(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        assert!(decode(&decode_args(&file), &mut Vec::new(), &Logger::default()).is_err());
+    }
+
+    #[test]
+    fn test_insert() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+
+        let args = InsertArgs {
+            filepath: file.clone(),
+            chunk_type: "ruSt".to_string(),
+            message: "hello".to_string(),
+            index: 1,
+            ..Default::default()
+        };
+        insert(&args, &mut Vec::new(), &Logger::default()).unwrap();
+
+        let png = read_png(&file).unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "ruSt");
+        assert_eq!(png.chunks()[1].data(), b"hello");
+    }
+
+    #[test]
+    fn test_remove_several_types() {
+        let dir = TempDir::new().unwrap();
+        let file = testing_file(&dir);
+        for message in ["one", "two"] {
+            encode(
+                &encode_args(&file, message),
+                &mut Vec::new(),
+                &Logger::default(),
+            )
+            .unwrap();
+        }
+        let mut png = read_png(&file).unwrap();
+        png.encode("tIME", "noon").unwrap();
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        let args = RemoveArgs {
+            filepath: file.clone(),
+            chunk_types: vec!["ruSt".to_string(), "tIME".to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        remove(&args, &mut out, &Logger::default()).unwrap();
+        assert_eq!(out, b"removed 2 ruSt chunks\nremoved 1 tIME chunks\n");
+
+        let types: Vec<String> = read_png(&file)
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+
+        assert!(remove(&args, &mut Vec::new(), &Logger::default()).is_err());
+
+        let args = RemoveArgs {
+            filepath: file,
+            chunk_types: vec!["IDAT".to_string(), "ru5t".to_string()],
+            ..Default::default()
+        };
+        assert!(remove(&args, &mut Vec::new(), &Logger::default()).is_err());
+        assert_eq!(read_png(&args.filepath).unwrap().chunks().len(), 3);
+    }
+
+    fn apng_file(dir: &TempDir) -> String {
+        let file = testing_file(dir);
+        let mut png = read_png(&file).unwrap();
+        for (index, chunk_type) in [(1, "acTL"), (2, "fcTL"), (4, "fcTL"), (5, "fdAT")] {
+            let chunk = Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; 8]).unwrap();
+            png.insert_chunk_at(index, chunk).unwrap();
+        }
+        png.write_file(&file).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_list_apng_chunks() {
+        let dir = TempDir::new().unwrap();
+        let args = ListArgs {
+            filepaths: vec![apng_file(&dir)],
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        list(&args, &mut out, &Logger::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[1].starts_with("acTL") && lines[1].ends_with("  APNG control"));
+        assert!(lines[2].starts_with("fcTL") && lines[2].ends_with("  APNG control"));
+        assert!(lines[3].starts_with("IDAT") && !lines[3].contains("APNG"));
+        assert!(lines[5].starts_with("fdAT") && lines[5].ends_with("  APNG frame data"));
+
+        let args = InfoArgs {
+            filepath: args.filepaths[0].clone(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        info(&args, &mut out, &Logger::default()).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("  animation:    3 APNG control, 1 APNG frame data chunks\n"));
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// Errors the library raises that callers may want to tell apart. They come
+/// back boxed in `crate::Error`, so match on them after a `downcast_ref`.
+///
+/// The enum is `#[non_exhaustive]`: new variants can be added in a minor
+/// release without breaking anyone's `match`, so every `match` needs a
+/// wildcard arm.
+///
+/// ```
+/// use pngme::png::Png;
+/// use pngme::PngError;
+///
+/// let err = Png::from_bytes(b"not a png file").err().unwrap();
+/// let reason = match err.downcast_ref::<PngError>() {
+///     Some(PngError::InvalidSignature) => "not a PNG",
+///     Some(PngError::CrcMismatch) => "corrupt chunk",
+///     Some(_) => "other PNG error",
+///     None => "other error",
+/// };
+/// assert_eq!(reason, "not a PNG");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PngError {
+    /// The input doesn't start with the 8-byte PNG signature.
+    InvalidSignature,
+    /// A chunk's stored CRC doesn't match its type and data.
+    CrcMismatch,
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature => write!(f, "invalid header"),
+            PngError::CrcMismatch => write!(f, "CRC mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
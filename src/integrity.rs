@@ -0,0 +1,69 @@
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+
+/// Bytes of the SHA-256 digest kept in the header.
+pub const HASH_LEN: usize = 8;
+
+/// Bytes in front of a sealed payload: its length as a big-endian `u32`,
+/// then the first `HASH_LEN` bytes of its SHA-256.
+pub const HEADER_LEN: usize = 4 + HASH_LEN;
+
+/// Prepends the length and hash header to `payload`, so `unseal` can tell a
+/// complete, untouched payload from one that was cut short or edited.
+pub fn seal(payload: &[u8]) -> Result<Vec<u8>> {
+    let len = u32::try_from(payload.len()).map_err(|_| "payload is too long to seal")?;
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(&len.to_be_bytes());
+    data.extend_from_slice(&Sha256::digest(payload)[..HASH_LEN]);
+    data.extend_from_slice(payload);
+    Ok(data)
+}
+
+/// Checks the header written by `seal` and returns the payload after it.
+pub fn unseal(data: &[u8]) -> Result<&[u8]> {
+    if data.len() < HEADER_LEN {
+        Err("data is too short to hold an integrity header")?;
+    }
+    let len = u32::from_be_bytes(data[..4].try_into()?) as usize;
+    let payload = &data[HEADER_LEN..];
+    if payload.len() != len {
+        Err(format!(
+            "payload is {} bytes but its header says {}",
+            payload.len(),
+            len
+        ))?;
+    }
+    if Sha256::digest(payload)[..HASH_LEN] != data[4..HEADER_LEN] {
+        Err("payload does not match its hash; it has been modified")?;
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let sealed = seal(b"hello").unwrap();
+        assert_eq!(sealed.len(), HEADER_LEN + 5);
+        assert_eq!(&sealed[..4], &[0, 0, 0, 5]);
+        assert_eq!(unseal(&sealed).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_unseal_detects_tampering() {
+        let mut sealed = seal(b"hello").unwrap();
+        sealed[HEADER_LEN] ^= 1;
+        let err = unseal(&sealed).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "payload does not match its hash; it has been modified"
+        );
+
+        let sealed = seal(b"hello").unwrap();
+        assert!(unseal(&sealed[..sealed.len() - 1]).is_err());
+        assert!(unseal(&sealed[..3]).is_err());
+    }
+}
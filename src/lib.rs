@@ -0,0 +1,20 @@
+pub mod args;
+pub mod cache;
+pub mod chunk;
+pub mod chunk_reader;
+pub mod chunk_type;
+pub mod commands;
+pub mod error;
+pub mod integrity;
+pub mod logging;
+pub mod pack;
+pub mod png;
+pub mod png_writer;
+mod rng;
+pub mod split;
+pub mod text;
+
+pub use error::PngError;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+
+/// How much a command reports beyond its essential output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only essential output, such as decoded text, plus warnings
+    Quiet,
+    #[default]
+    Normal,
+    /// Also per-chunk diagnostics on stderr
+    Verbose,
+}
+
+/// Routes the messages commands produce according to the verbosity.
+///
+/// Informational messages go to the command's output, while diagnostics and
+/// warnings go to stderr so they never mix with data written to stdout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger {
+    verbosity: Verbosity,
+    progress: bool,
+    info_on_stderr: bool,
+}
+
+impl Logger {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Logger {
+            verbosity,
+            progress: false,
+            info_on_stderr: false,
+        }
+    }
+
+    /// Enables progress reports for long reads and writes. They are only
+    /// drawn when stderr is a terminal.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Sends informational messages to stderr instead of the command's
+    /// output, for commands writing a PNG to stdout.
+    pub fn with_info_on_stderr(mut self, info_on_stderr: bool) -> Self {
+        self.info_on_stderr = info_on_stderr;
+        self
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    /// Writes a success or progress message, unless running quietly.
+    pub fn info(&self, out: &mut impl Write, message: fmt::Arguments) -> io::Result<()> {
+        if self.verbosity > Verbosity::Quiet {
+            if self.info_on_stderr {
+                eprintln!("{}", message);
+            } else {
+                writeln!(out, "{}", message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes essential output, shown even when running quietly, to stderr
+    /// instead of the command's output under the same rule as [`Self::info`].
+    pub fn output(&self, out: &mut impl Write, message: fmt::Arguments) -> io::Result<()> {
+        if self.info_on_stderr {
+            eprintln!("{}", message);
+        } else {
+            writeln!(out, "{}", message)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a diagnostic to stderr when running verbosely.
+    pub fn debug(&self, message: fmt::Arguments) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("debug: {}", message);
+        }
+    }
+
+    /// Redraws a progress line on stderr showing `done` of `total` bytes,
+    /// finishing the line once everything is done.
+    pub fn progress(&self, action: &str, done: u64, total: u64) {
+        if !self.progress || self.verbosity == Verbosity::Quiet || !io::stderr().is_terminal() {
+            return;
+        }
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        eprint!(
+            "\r{}: {:>3}% ({} of {} bytes)",
+            action, percent, done, total
+        );
+        if done >= total {
+            eprintln!();
+        }
+    }
+
+    /// Writes a warning to stderr; warnings are shown at every verbosity.
+    pub fn warn(&self, message: fmt::Arguments) {
+        eprintln!("warning: {}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_respects_quiet() {
+        let mut out = Vec::new();
+        Logger::default()
+            .info(&mut out, format_args!("wrote {} bytes", 3))
+            .unwrap();
+        assert_eq!(out, b"wrote 3 bytes\n");
+
+        let mut out = Vec::new();
+        Logger::new(Verbosity::Quiet)
+            .info(&mut out, format_args!("wrote {} bytes", 3))
+            .unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_output_ignores_quiet() {
+        let mut out = Vec::new();
+        Logger::new(Verbosity::Quiet)
+            .output(&mut out, format_args!("chunk type: {}", "ruSt"))
+            .unwrap();
+        assert_eq!(out, b"chunk type: ruSt\n");
+
+        let mut out = Vec::new();
+        Logger::default()
+            .with_info_on_stderr(true)
+            .output(&mut out, format_args!("chunk type: {}", "ruSt"))
+            .unwrap();
+        assert!(out.is_empty());
+    }
+}
@@ -1,17 +1,26 @@
-use clap::Parser;
+use std::io;
+use std::process::ExitCode;
 
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
+use clap::Parser;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+use pngme::logging::Logger;
+use pngme::{args, commands};
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = args::Cli::parse();
+    // The library's parse tracing stays off unless asked for with RUST_LOG,
+    // e.g. RUST_LOG=pngme=trace; --verbose has diagnostics of its own
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("off")).init();
+    let logger = Logger::new(cli.verbosity()).with_progress(cli.progress);
 
-    println!("{:?}", cli);
-    Ok(())
+    match commands::run(&cli.command, &mut io::stdout(), &logger) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            match e.downcast_ref::<commands::CheckFailed>() {
+                Some(failed) => ExitCode::from(failed.exit_code),
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
 }
@@ -0,0 +1,67 @@
+use crate::Result;
+
+/// Packs named values into one buffer. Each entry is a 1-byte name length,
+/// the UTF-8 name, a 4-byte big-endian value length, then the value.
+pub fn pack_entries(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for (name, value) in entries {
+        let name_len = u8::try_from(name.len())
+            .map_err(|_| format!("entry name '{}' is longer than 255 bytes", name))?;
+        let value_len = u32::try_from(value.len())
+            .map_err(|_| format!("entry '{}' is longer than {} bytes", name, u32::MAX))?;
+
+        data.push(name_len);
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&value_len.to_be_bytes());
+        data.extend_from_slice(value);
+    }
+    Ok(data)
+}
+
+/// Reads back the entries written by `pack_entries`, in order.
+pub fn unpack_entries(mut data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    while let Some((&name_len, rest)) = data.split_first() {
+        let name_len = name_len as usize;
+        if rest.len() < name_len + 4 {
+            Err("packed entry is cut short")?;
+        }
+        let name = String::from_utf8(rest[..name_len].to_vec())
+            .map_err(|_| "packed entry name is not valid UTF-8")?;
+        let value_len = u32::from_be_bytes(rest[name_len..name_len + 4].try_into()?) as usize;
+
+        let rest = &rest[name_len + 4..];
+        if rest.len() < value_len {
+            Err(format!("packed entry '{}' is cut short", name))?;
+        }
+        entries.push((name, rest[..value_len].to_vec()));
+        data = &rest[value_len..];
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let entries = vec![
+            ("user".to_string(), b"alice".to_vec()),
+            ("empty".to_string(), vec![]),
+            ("key".to_string(), vec![0, 255, 10]),
+        ];
+        let data = pack_entries(&entries).unwrap();
+        assert_eq!(&data[..10], b"\x04user\x00\x00\x00\x05a");
+        assert_eq!(unpack_entries(&data).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_unpack_truncated() {
+        let data = pack_entries(&[("user".to_string(), b"alice".to_vec())]).unwrap();
+        let err = unpack_entries(&data[..data.len() - 1]).unwrap_err();
+        assert_eq!(err.to_string(), "packed entry 'user' is cut short");
+        assert!(unpack_entries(&data[..3]).is_err());
+        assert!(pack_entries(&[("n".repeat(256), vec![])]).is_err());
+    }
+}
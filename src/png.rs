@@ -1,238 +1,2112 @@
 use core::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Index;
+use std::path::Path;
 use std::str::FromStr;
 
-use crate::{chunk::Chunk, chunk_type::ChunkType};
-use crate::{Error, Result};
+use crc::Crc;
+use flate2::read::GzDecoder;
+
+use crate::chunk::{Chunk, PNG_CRC};
+use crate::chunk_type::ChunkType;
+use crate::{Error, PngError, Result};
+
+/// Size of the buffer `write_to` gathers small writes in, so that a file of
+/// many small chunks doesn't cost a system call per chunk.
+pub const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Checks and limits applied while parsing a PNG.
+#[derive(Clone)]
+pub struct ParseOptions {
+    /// Upper bound on the number of chunks, to guard against pathological input
+    pub max_chunks: usize,
+    /// Reject chunks whose stored CRC doesn't match their data
+    pub check_crc: bool,
+    /// CRC-32 variant stored CRCs are checked against; the PNG one by default
+    pub crc: &'static Crc<u32>,
+    /// Reject files with more than one `IHDR` or `IEND` chunk
+    pub reject_duplicates: bool,
+    /// Require the 8-byte PNG signature; without it the input is read as a
+    /// bare sequence of chunks, as some other containers store them
+    pub expect_signature: bool,
+    /// Read chunks longer than `Chunk::MAX_LENGTH` with a warning instead of
+    /// rejecting them, to recover data from files that break the limit
+    pub allow_oversize: bool,
+    /// On a chunk that fails to parse, skip ahead to the next plausible chunk
+    /// instead of giving up; see `Png::skipped_regions`
+    pub recover: bool,
+}
+
+impl fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("max_chunks", &self.max_chunks)
+            .field("check_crc", &self.check_crc)
+            .field(
+                "crc_poly",
+                &format_args!("{:#010x}", self.crc.algorithm.poly),
+            )
+            .field("reject_duplicates", &self.reject_duplicates)
+            .field("expect_signature", &self.expect_signature)
+            .field("allow_oversize", &self.allow_oversize)
+            .field("recover", &self.recover)
+            .finish()
+    }
+}
+
+impl ParseOptions {
+    /// Options that accept chunks with bad CRCs and duplicate `IHDR` or
+    /// `IEND` chunks, for inspecting damaged files.
+    pub fn lenient() -> Self {
+        ParseOptions {
+            check_crc: false,
+            reject_duplicates: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_chunks: 100_000,
+            check_crc: true,
+            crc: &PNG_CRC,
+            reject_duplicates: true,
+            expect_signature: true,
+            allow_oversize: false,
+            recover: false,
+        }
+    }
+}
+
+/// The image header stored in the `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    /// Length of the `IHDR` chunk data.
+    pub const LENGTH: usize = 13;
+
+    /// Name of the colour type, if it's one the PNG specification defines.
+    pub fn color_type_name(&self) -> Option<&'static str> {
+        match self.color_type {
+            0 => Some("greyscale"),
+            2 => Some("RGB"),
+            3 => Some("indexed"),
+            4 => Some("greyscale with alpha"),
+            6 => Some("RGBA"),
+            _ => None,
+        }
+    }
+
+    /// Name of the interlace method, if it's one the PNG specification defines.
+    pub fn interlace_name(&self) -> Option<&'static str> {
+        match self.interlace_method {
+            0 => Some("none"),
+            1 => Some("Adam7"),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = Error;
+    fn try_from(data: &[u8]) -> Result<Self> {
+        if data.len() != Ihdr::LENGTH {
+            Err(format!(
+                "IHDR data must be {} bytes, got {}",
+                Ihdr::LENGTH,
+                data.len()
+            ))?;
+        }
+
+        Ok(Ihdr {
+            width: u32::from_be_bytes(data[0..4].try_into()?),
+            height: u32::from_be_bytes(data[4..8].try_into()?),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, bit depth {}, color type {}, compression {}, filter {}, interlace {}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            self.compression_method,
+            self.filter_method,
+            self.interlace_method
+        )
+    }
+}
+
+/// The image's last-modification time, stored in UTC in the `tIME` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    /// Length of the `tIME` chunk data.
+    pub const LENGTH: usize = 7;
+}
+
+impl TryFrom<&[u8]> for Time {
+    type Error = Error;
+    fn try_from(data: &[u8]) -> Result<Self> {
+        if data.len() != Time::LENGTH {
+            Err(format!(
+                "tIME data must be {} bytes, got {}",
+                Time::LENGTH,
+                data.len()
+            ))?;
+        }
+
+        let time = Time {
+            year: u16::from_be_bytes(data[0..2].try_into()?),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        };
+        // A second of 60 allows for leap seconds, as the specification does
+        let fields = [
+            ("month", time.month, 1, 12),
+            ("day", time.day, 1, 31),
+            ("hour", time.hour, 0, 23),
+            ("minute", time.minute, 0, 59),
+            ("second", time.second, 0, 60),
+        ];
+        for (name, value, min, max) in fields {
+            if !(min..=max).contains(&value) {
+                Err(format!(
+                    "tIME {} {} is outside {}-{}",
+                    name, value, min, max
+                ))?;
+            }
+        }
+
+        Ok(time)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Where the PNG specification requires an ancillary chunk type to appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    /// Before `PLTE` and the first `IDAT`
+    BeforePlte,
+    /// After `PLTE` (if present) and before the first `IDAT`
+    BeforeIdat,
+    /// Anywhere between `IHDR` and `IEND`
+    Anywhere,
+}
+
+impl Placement {
+    fn of(chunk_type: &ChunkType) -> Option<Placement> {
+        match &chunk_type.bytes() {
+            b"cHRM" | b"gAMA" | b"iCCP" | b"sBIT" | b"sRGB" => Some(Placement::BeforePlte),
+            b"bKGD" | b"hIST" | b"tRNS" | b"pHYs" | b"sPLT" => Some(Placement::BeforeIdat),
+            b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => Some(Placement::Anywhere),
+            _ => None,
+        }
+    }
+}
+
+/// What `Png::parse_chunks_until` read.
+struct ParsedChunks {
+    chunks: Vec<Chunk>,
+    /// Offset just past the last chunk read
+    end: usize,
+    /// (offset, length) of each region `ParseOptions::recover` skipped
+    skipped: Vec<(usize, usize)>,
+}
 
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailing: Vec<u8>,
+    skipped: Vec<(usize, usize)>,
 }
 
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self {
-            header: Png::STANDARD_HEADER,
-            chunks,
+    /// How far past a damaged chunk `ParseOptions::recover` looks for the
+    /// next one; anything after a longer damaged region is skipped as well.
+    pub const RECOVERY_SCAN_LIMIT: usize = 1024 * 1024;
+
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        Self {
+            header: Png::STANDARD_HEADER,
+            chunks,
+            trailing: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        self.chunks.push(chunk);
+    }
+
+    /// Puts the chunks in one canonical order, so files holding the same
+    /// chunks come out the same. The ancillary chunk types the specification
+    /// places relative to `PLTE` and `IDAT` move into allowed positions, and
+    /// text and `tIME`, which may go anywhere, move to just before `IEND`.
+    /// Critical, APNG and unknown chunks keep their order relative to each
+    /// other, and the ancillary chunks between them are sorted by type and
+    /// then data.
+    pub fn reorder_canonical(&mut self) {
+        let mut before_plte = Vec::new();
+        let mut before_idat = Vec::new();
+        let mut anywhere = Vec::new();
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in self.chunks.drain(..) {
+            match Placement::of(chunk.chunk_type()) {
+                Some(Placement::BeforePlte) => before_plte.push(chunk),
+                Some(Placement::BeforeIdat) => before_idat.push(chunk),
+                Some(Placement::Anywhere) => anywhere.push(chunk),
+                None => chunks.push(chunk),
+            }
+        }
+
+        let is_type = |chunk: &Chunk, name: &str| chunk.chunk_type() == name;
+
+        // PLTE, when present, always comes before the first IDAT
+        let idat = chunks
+            .iter()
+            .position(|c| is_type(c, "IDAT"))
+            .or_else(|| chunks.iter().position(|c| is_type(c, "IEND")))
+            .unwrap_or(chunks.len());
+        chunks.splice(idat..idat, before_idat);
+
+        let start = usize::from(chunks.first().is_some_and(|c| is_type(c, "IHDR")));
+        chunks.splice(start..start, before_plte);
+
+        let iend = chunks
+            .iter()
+            .position(|c| is_type(c, "IEND"))
+            .unwrap_or(chunks.len());
+        chunks.splice(iend..iend, anywhere);
+
+        // Unknown chunks have to stay between the same critical chunks, and
+        // APNG frames depend on their order, but nothing else does
+        let fixed = |c: &Chunk| c.is_critical() || c.chunk_type().apng_role().is_some();
+        for run in chunks.split_mut(fixed) {
+            run.sort_by(|a, b| {
+                let types = a.chunk_type().bytes().cmp(&b.chunk_type().bytes());
+                types.then_with(|| a.data().cmp(b.data()))
+            });
+        }
+
+        self.chunks = chunks;
+    }
+
+    /// Stores `message` in a new chunk of type `type_str`, placed just before
+    /// `IEND` (or at the end if there is no `IEND`).
+    pub fn encode(&mut self, type_str: &str, message: &str) -> Result<()> {
+        let chunk_type = ChunkType::from_str(type_str)?;
+        let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec())?;
+        self.insert_before_iend(chunk);
+        Ok(())
+    }
+
+    /// Adds `chunk` just before `IEND`, or at the end if there is no `IEND`,
+    /// so it stays inside the part of the file decoders read.
+    pub fn insert_before_iend(&mut self, chunk: Chunk) {
+        let iend = self.chunks.iter().position(|c| c.chunk_type() == "IEND");
+        match iend {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.chunks.push(chunk),
+        }
+    }
+
+    /// Adds `chunk` straight after the last chunk of type `anchor_type`, for
+    /// chunks the specification orders relative to another, such as tRNS
+    /// after PLTE. Fails if there is no anchor chunk or it is IEND.
+    pub fn insert_after_type(&mut self, anchor_type: &str, chunk: Chunk) -> Result<()> {
+        let index = self.index_after_type(anchor_type)?;
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// The index `insert_after_type` would give a chunk inserted after the
+    /// last chunk of type `anchor_type`.
+    pub fn index_after_type(&self, anchor_type: &str) -> Result<usize> {
+        let anchor = ChunkType::from_str(anchor_type)?;
+        if anchor == "IEND" {
+            Err("cannot insert a chunk after IEND")?;
+        }
+        let index = self
+            .chunks
+            .iter()
+            .rposition(|c| c.chunk_type() == &anchor)
+            .ok_or_else(|| format!("no {} chunk to insert after", anchor))?;
+        Ok(index + 1)
+    }
+
+    /// Puts `new_chunk` in place of the first chunk of type `type_str`,
+    /// keeping its position; returns `false`, leaving the file as it was, if
+    /// there is no such chunk.
+    pub fn replace_chunk(&mut self, type_str: &str, new_chunk: Chunk) -> Result<bool> {
+        let chunk_type = ChunkType::from_str(type_str)?;
+        match self
+            .chunks
+            .iter_mut()
+            .find(|c| c.chunk_type() == &chunk_type)
+        {
+            Some(chunk) => {
+                *chunk = new_chunk;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Adds `data` to the end of the first chunk of type `type_str`, updating
+    /// its length and CRC. Returns `false`, changing nothing, if there is no
+    /// such chunk.
+    pub fn append_to_chunk(&mut self, type_str: &str, data: &[u8]) -> Result<bool> {
+        let chunk_type = ChunkType::from_str(type_str)?;
+        match self
+            .chunks
+            .iter_mut()
+            .find(|c| c.chunk_type() == &chunk_type)
+        {
+            Some(chunk) => {
+                let mut joined = chunk.data().to_vec();
+                joined.extend_from_slice(data);
+                chunk.set_data(joined)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The data of the first chunk of type `type_str` as UTF-8 text, or `None`
+    /// if there is no such chunk.
+    pub fn decode(&self, type_str: &str) -> Result<Option<String>> {
+        let chunk_type = ChunkType::from_str(type_str)?;
+        match self.chunks.iter().find(|c| c.chunk_type() == &chunk_type) {
+            Some(chunk) => Ok(Some(chunk.data_as_string()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Inserts `chunk` so that it ends up at `index`, refusing positions in
+    /// front of `IHDR` or behind `IEND`.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            Err(format!(
+                "index {} is out of range for {} chunks",
+                index,
+                self.chunks.len()
+            ))?;
+        }
+
+        if index == 0
+            && self
+                .chunks
+                .first()
+                .is_some_and(|c| c.chunk_type() == "IHDR")
+        {
+            Err("cannot insert a chunk before IHDR")?;
+        }
+
+        if let Some(iend_index) = self.chunks.iter().position(|c| c.chunk_type() == "IEND") {
+            if index > iend_index {
+                Err("cannot insert a chunk after IEND")?;
+            }
+        }
+
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Removes every chunk of `chunk_type`, returning them in their original
+    /// order.
+    pub fn remove_chunks_of_type(&mut self, chunk_type: &ChunkType) -> Vec<Chunk> {
+        self.take_chunks_where(|chunk| chunk.chunk_type() == chunk_type)
+    }
+
+    /// Removes every chunk for which `predicate` returns true, keeping the
+    /// rest in order. Returns how many chunks were removed.
+    pub fn remove_chunks_where<F: Fn(&Chunk) -> bool>(&mut self, predicate: F) -> usize {
+        self.take_chunks_where(predicate).len()
+    }
+
+    fn take_chunks_where<F: Fn(&Chunk) -> bool>(&mut self, predicate: F) -> Vec<Chunk> {
+        let (removed, kept) = self.chunks.drain(..).partition(|chunk| predicate(chunk));
+        self.chunks = kept;
+        removed
+    }
+
+    /// Gives every chunk of type `from` the type `to`, keeping its data and
+    /// position. Returns how many chunks were relabelled.
+    pub fn relabel_chunks(&mut self, from: &ChunkType, to: &ChunkType) -> usize {
+        let mut relabelled = 0;
+        self.chunks = self
+            .chunks
+            .drain(..)
+            .map(|chunk| {
+                if chunk.chunk_type() == from {
+                    relabelled += 1;
+                    chunk.with_type(to.clone())
+                } else {
+                    chunk
+                }
+            })
+            .collect();
+        relabelled
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let index = self.get_index_for_type(chunk_type)?;
+        Ok(self.chunks.remove(index))
+    }
+
+    /// Removes every ancillary chunk whose type isn't listed in `keep`,
+    /// returning the removed chunks in their original order.
+    pub fn strip_ancillary(&mut self, keep: &[ChunkType]) -> Vec<Chunk> {
+        self.take_chunks_where(|chunk| chunk.is_ancillary() && !keep.contains(chunk.chunk_type()))
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        match self.get_index_for_type(chunk_type) {
+            Ok(index) => Some(&self.chunks[index]),
+            Err(_) => None,
+        }
+    }
+
+    /// The data of every chunk of type `type_str` joined end to end in file
+    /// order, such as all the `IDAT` data as one buffer. Empty if none match.
+    pub fn data_of_all(&self, type_str: &str) -> Vec<u8> {
+        self.chunks_by_type(type_str)
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect()
+    }
+
+    /// Every chunk of type `type_str`, in file order. A string that isn't a
+    /// valid chunk type matches nothing; validate it first with
+    /// `ChunkType::from_str` to report it.
+    pub fn chunks_by_type<'a>(&'a self, type_str: &str) -> impl Iterator<Item = &'a Chunk> {
+        let wanted = type_str.as_bytes().to_vec();
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.chunk_type().bytes() == *wanted)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&self.header);
+
+        for chunk in &self.chunks {
+            bytes.extend_from_slice(&Chunk::as_bytes(chunk));
+        }
+        bytes.extend_from_slice(&self.trailing);
+        bytes
+    }
+
+    /// Writes the signature and then every chunk, flushing at the end.
+    /// Writes pass through a buffer of `WRITE_BUFFER_SIZE` bytes, so `writer`
+    /// needn't be buffered already.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_to_with_progress(writer, |_| {})
+    }
+
+    /// Like `write_to`, but calls `progress` with the number of bytes written
+    /// so far after the signature, each chunk and the trailing bytes.
+    pub fn write_to_with_progress<W: Write>(
+        &self,
+        writer: W,
+        mut progress: impl FnMut(u64),
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::with_capacity(WRITE_BUFFER_SIZE, writer);
+        let mut written = self.header.len() as u64;
+        writer.write_all(&self.header)?;
+        progress(written);
+        for chunk in &self.chunks {
+            writer.write_all(&chunk.as_bytes())?;
+            written += chunk.serialized_len() as u64;
+            progress(written);
+        }
+        if !self.trailing.is_empty() {
+            writer.write_all(&self.trailing)?;
+            written += self.trailing.len() as u64;
+            progress(written);
+        }
+        writer.flush()
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(File::create(path)?)
+    }
+
+    /// Recomputes every chunk's CRC from its data, returning how many of the
+    /// stored CRCs were wrong.
+    pub fn recalculate_crcs(&mut self) -> usize {
+        let mut changed = 0;
+        for chunk in &mut self.chunks {
+            let old_crc = chunk.crc();
+            chunk.recalculate_crc();
+            if chunk.crc() != old_crc {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Length of the file `as_bytes` would produce.
+    pub fn total_size(&self) -> usize {
+        self.header.len()
+            + self.chunks.iter().map(Chunk::serialized_len).sum::<usize>()
+            + self.trailing.len()
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
+        &self.header
+    }
+
+    /// Replaces the 8-byte signature written in front of the chunks. Anything
+    /// but `STANDARD_HEADER` makes the output an invalid PNG, which is only
+    /// useful for testing how other parsers cope.
+    pub fn set_header(&mut self, header: [u8; 8]) {
+        self.header = header;
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Number of chunks in the file, not counting the signature.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Same as `len`, for call sites where "len" of a file reads ambiguously.
+    pub fn chunk_count(&self) -> usize {
+        self.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Mutable access to the chunks; use `Chunk::set_data` to change a chunk's
+    /// data so its length and CRC stay in step.
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        &mut self.chunks
+    }
+
+    /// First bytes of a gzip stream.
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+    /// The most bytes a gzip-compressed PNG may inflate to, so a small
+    /// `.png.gz` can't expand into more than memory holds.
+    pub const MAX_GZIP_INFLATED: u64 = 1024 * 1024 * 1024;
+
+    /// Reads a whole PNG from `reader`, transparently inflating it first if
+    /// it is gzip-compressed, as `.png.gz` files are.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Png> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if bytes.starts_with(&Png::GZIP_MAGIC) {
+            let inflated = inflate_gzip(&bytes, Png::MAX_GZIP_INFLATED)?;
+            return Png::from_bytes(&inflated);
+        }
+        Png::from_bytes(&bytes)
+    }
+
+    /// Reads and parses the file at `path`. Failing to open it is reported
+    /// as "cannot open PATH: ...", so it can't be mistaken for a bad PNG.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Png> {
+        Png::from_reader(open_file(path.as_ref())?)
+    }
+
+    /// Parses a PNG file strictly, signature included. Nothing is normalised
+    /// on the way in, so for any file this accepts, `as_bytes` gives back
+    /// exactly the same bytes. That doesn't hold for every `from_bytes_with`
+    /// option: a bare chunk stream gains a signature, and `recover` drops the
+    /// regions it skips.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Png> {
+        Png::from_bytes_with(bytes, &ParseOptions::default())
+    }
+
+    /// Parses a PNG file, or with `expect_signature` off a bare chunk stream,
+    /// which is given the standard signature.
+    pub fn from_bytes_with(input_bytes: &[u8], options: &ParseOptions) -> Result<Png> {
+        let start = if options.expect_signature {
+            Png::check_signature(input_bytes)?;
+            Png::STANDARD_HEADER.len()
+        } else {
+            0
+        };
+
+        // Anything after IEND isn't part of the image, so it's kept aside
+        let parsed = Png::parse_chunks_until(input_bytes, start, options, true)?;
+        let trailing = input_bytes[parsed.end..].to_vec();
+
+        let png = Png {
+            header: Png::STANDARD_HEADER,
+            chunks: parsed.chunks,
+            trailing,
+            skipped: parsed.skipped,
+        };
+        let duplicates = png.duplicate_ihdr_iend();
+        if options.reject_duplicates && !duplicates.is_empty() {
+            // Offsets count the signature, which a chunk stream doesn't have
+            let shift = Png::STANDARD_HEADER.len() - start;
+            let found: Vec<String> = duplicates
+                .iter()
+                .map(|(name, offset)| format!("{} at offset {:#x}", name, offset - shift))
+                .collect();
+            Err(format!("duplicate chunks: {}", found.join(", ")))?;
+        }
+        Ok(png)
+    }
+
+    fn check_signature(input_bytes: &[u8]) -> Result<()> {
+        if input_bytes.len() < 8 {
+            Err(PngError::InvalidSignature)?;
+        }
+        if input_bytes[0..8] != Png::STANDARD_HEADER {
+            log::error!(
+                "signature {:02x?} is not the PNG signature",
+                &input_bytes[0..8]
+            );
+            Err(PngError::InvalidSignature)?;
+        }
+        Ok(())
+    }
+
+    /// Parses a bare sequence of chunks, with no signature in front of them.
+    pub fn parse_chunks(bytes: &[u8], options: &ParseOptions) -> Result<Vec<Chunk>> {
+        Ok(Png::parse_chunks_until(bytes, 0, options, false)?.chunks)
+    }
+
+    /// Lists the type, offset and data length of each chunk up to `IEND`,
+    /// walking only the chunk headers so no chunk's data is copied. With
+    /// `check_crc` the data is still read to check each CRC.
+    pub fn index(bytes: &[u8], options: &ParseOptions) -> Result<Vec<(ChunkType, usize, u32)>> {
+        let mut offset = if options.expect_signature {
+            Png::check_signature(bytes)?;
+            Png::STANDARD_HEADER.len()
+        } else {
+            0
+        };
+
+        let mut index = Vec::new();
+        while offset < bytes.len() {
+            if index.len() == options.max_chunks {
+                Err(format!("file has more than {} chunks", options.max_chunks))?;
+            }
+
+            let chunk = Chunk::peek(&bytes[offset..], options)
+                .map_err(|e| format!("chunk parse error at offset {:#x}: {}", offset, e))?;
+            if options.check_crc && !chunk.crc_matches(options.crc) {
+                Err(format!(
+                    "chunk parse error at offset {:#x}: {}",
+                    offset,
+                    PngError::CrcMismatch
+                ))?;
+            }
+
+            let is_iend = chunk.chunk_type == "IEND";
+            index.push((chunk.chunk_type, offset, chunk.length));
+            offset += 12 + chunk.data.len();
+            if is_iend {
+                break;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Parses chunks from `start` until the bytes run out, or `stop_at_iend`
+    /// and an `IEND` chunk has been read. Errors name the offset of the chunk
+    /// that failed.
+    fn parse_chunks_until(
+        bytes: &[u8],
+        start: usize,
+        options: &ParseOptions,
+        stop_at_iend: bool,
+    ) -> Result<ParsedChunks> {
+        let mut chunks = Vec::new();
+        let mut skipped = Vec::new();
+        let mut offset = start;
+
+        // Create chunks until there are no more bytes
+        while offset < bytes.len() {
+            if chunks.len() == options.max_chunks {
+                Err(format!("file has more than {} chunks", options.max_chunks))?;
+            }
+
+            let chunk = match Chunk::from_slice(&bytes[offset..], options) {
+                Ok(chunk) => chunk,
+                Err(e) if options.recover => {
+                    let next = Png::next_plausible_chunk(bytes, offset + 1, options);
+                    let next = next.unwrap_or(bytes.len());
+                    log::warn!(
+                        "skipping {} bytes at offset {:#x}: {}",
+                        next - offset,
+                        offset,
+                        e
+                    );
+                    skipped.push((offset, next - offset));
+                    offset = next;
+                    continue;
+                }
+                Err(e) => {
+                    let message = format!("chunk parse error at offset {:#x}: {}", offset, e);
+                    log::error!("{}", message);
+                    Err(message)?
+                }
+            };
+            log::trace!(
+                "{} chunk at offset {:#x}, {} bytes",
+                chunk.chunk_type(),
+                offset,
+                chunk.length()
+            );
+            if !chunk.crc_ok() {
+                log::warn!(
+                    "{} chunk at offset {:#x} has a bad CRC",
+                    chunk.chunk_type(),
+                    offset
+                );
+            }
+            offset += chunk.serialized_len();
+
+            let is_iend = chunk.chunk_type() == "IEND";
+            chunks.push(chunk);
+            if stop_at_iend && is_iend {
+                break;
+            }
+        }
+
+        Ok(ParsedChunks {
+            chunks,
+            end: offset,
+            skipped,
+        })
+    }
+
+    /// The first offset from `from` where a whole chunk with a four-letter
+    /// type, a length that fits and a matching CRC can be read, looking at
+    /// most `RECOVERY_SCAN_LIMIT` bytes ahead.
+    fn next_plausible_chunk(bytes: &[u8], from: usize, options: &ParseOptions) -> Option<usize> {
+        let end = bytes
+            .len()
+            .saturating_sub(11)
+            .min(from.saturating_add(Png::RECOVERY_SCAN_LIMIT));
+        (from..end).find(|&offset| {
+            bytes[offset + 4..offset + 8]
+                .iter()
+                .all(u8::is_ascii_alphabetic)
+                && Chunk::peek(&bytes[offset..], options).is_ok_and(|c| c.crc_matches(options.crc))
+        })
+    }
+
+    /// Damaged regions of the input that parsing with `recover` skipped, as
+    /// (offset, length) pairs. They are not written back out.
+    pub fn skipped_regions(&self) -> &[(usize, usize)] {
+        &self.skipped
+    }
+
+    /// Every `IHDR` or `IEND` chunk after the first of its type, with its
+    /// offset in the file. Parsing stops at the first `IEND`, so a second one
+    /// straight after it is found at the start of the trailing bytes.
+    pub fn duplicate_ihdr_iend(&self) -> Vec<(&'static str, usize)> {
+        let offsets = self.chunk_offsets();
+        let mut found = Vec::new();
+        for name in ["IHDR", "IEND"] {
+            let mut matching = self
+                .chunks
+                .iter()
+                .zip(&offsets)
+                .filter(|(chunk, _)| chunk.chunk_type() == name);
+            matching.next();
+            found.extend(matching.map(|(_, &offset)| (name, offset)));
+        }
+
+        if self.trailing.starts_with(&[0, 0, 0, 0]) && self.trailing.get(4..8) == Some(b"IEND") {
+            found.push(("IEND", self.total_size() - self.trailing.len()));
+        }
+        found.sort_by_key(|&(_, offset)| offset);
+        found
+    }
+
+    /// The byte offset within the file of each chunk, in order, assuming the
+    /// chunks follow the signature as they do when written out.
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        let mut offset = self.header.len();
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let start = offset;
+                offset += chunk.serialized_len();
+                start
+            })
+            .collect()
+    }
+
+    /// Bytes that followed the `IEND` chunk in the parsed file. Decoders
+    /// ignore them, which makes them a common hiding place for payloads.
+    pub fn trailing_bytes(&self) -> &[u8] {
+        &self.trailing
+    }
+
+    /// Discards the trailing bytes so they aren't written back out, returning
+    /// them.
+    pub fn take_trailing_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailing)
+    }
+
+    /// The parsed image header, or an error if `IHDR` is missing or malformed.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let chunk = self.chunk_by_type("IHDR").ok_or("IHDR chunk not found")?;
+        Ihdr::try_from(chunk.data())
+    }
+
+    /// The time stored in the `tIME` chunk, or `None` if there isn't one.
+    pub fn modification_time(&self) -> Option<Result<Time>> {
+        self.chunk_by_type("tIME")
+            .map(|chunk| Time::try_from(chunk.data()))
+    }
+
+    /// Ancillary chunks whose type isn't defined by the PNG specification,
+    /// which is where user-embedded data usually lives.
+    pub fn hidden_chunks(&self) -> Vec<&Chunk> {
+        self.chunks
+            .iter()
+            .filter(|chunk| {
+                let chunk_type = chunk.chunk_type();
+                chunk.is_ancillary()
+                    && chunk_type.standard_description().is_none()
+                    && chunk_type.apng_role().is_none()
+            })
+            .collect()
+    }
+
+    /// The per-channel significant bit counts from the `sBIT` chunk, if there
+    /// is one and its length matches the colour type declared in `IHDR`.
+    pub fn significant_bits(&self) -> Option<Vec<u8>> {
+        let color_type = *self.chunk_by_type("IHDR")?.data().get(9)?;
+        let expected_len = match color_type {
+            0 => 1,     // greyscale
+            2 | 3 => 3, // truecolour, indexed
+            4 => 2,     // greyscale with alpha
+            6 => 4,     // truecolour with alpha
+            _ => return None,
+        };
+
+        let data = self.chunk_by_type("sBIT")?.data();
+        if data.len() != expected_len {
+            return None;
+        }
+        Some(data.to_vec())
+    }
+
+    /// Describes the problem when an indexed-colour image has a `tRNS` chunk
+    /// with more alpha entries than `PLTE` has palette entries.
+    pub fn palette_transparency_problem(&self) -> Option<String> {
+        let color_type = *self.chunk_by_type("IHDR")?.data().get(9)?;
+        if color_type != 3 {
+            return None;
+        }
+
+        let entries = self.chunk_by_type("PLTE")?.data().len() / 3;
+        let alphas = self.chunk_by_type("tRNS")?.data().len();
+        if alphas <= entries {
+            return None;
+        }
+        Some(format!(
+            "tRNS chunk has {} alpha entries but PLTE has only {} palette entries",
+            alphas, entries
+        ))
+    }
+
+    fn get_index_for_type(&self, chunk_type: &str) -> Result<usize> {
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type() == &chunk_type)
+            .ok_or("chunk not found")?;
+
+        Ok(index)
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+    fn try_from(input_bytes: &[u8]) -> Result<Self> {
+        Png::from_bytes(input_bytes)
+    }
+}
+
+/// Treats the string as a file path, so `let png: Png = "a.png".try_into()?`
+/// loads a file. Use `from_bytes` for data already in memory.
+impl TryFrom<&str> for Png {
+    type Error = Error;
+    fn try_from(path: &str) -> Result<Self> {
+        Png::from_file(path)
+    }
+}
+
+/// A valid 1x1 image holding one black 8-bit greyscale pixel, to build on.
+impl Default for Png {
+    fn default() -> Self {
+        // Width 1, height 1, bit depth 8, greyscale, then the three methods
+        let ihdr = vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        // zlib stream of the one scanline: filter type 0, then pixel value 0
+        let idat = vec![0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
+
+        let chunk = |name, data| {
+            Chunk::new(ChunkType::from_ascii(name), data).expect("data is well under the limit")
+        };
+        Png::from_chunks(vec![
+            chunk(b"IHDR", ihdr),
+            chunk(b"IDAT", idat),
+            Chunk::empty(ChunkType::from_ascii(b"IEND")),
+        ])
+    }
+}
+
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = std::slice::Iter<'a, Chunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter()
+    }
+}
+
+/// `png[i]` is the `i`th chunk. Like indexing a `Vec`, it panics when `i` is
+/// out of range; use `chunks().get(i)` to check instead.
+impl Index<usize> for Png {
+    type Output = Chunk;
+
+    fn index(&self, index: usize) -> &Chunk {
+        &self.chunks[index]
+    }
+}
+
+impl AsRef<[Chunk]> for Png {
+    fn as_ref(&self) -> &[Chunk] {
+        &self.chunks
+    }
+}
+
+/// Inflates a gzip stream, failing once it grows past `limit` bytes.
+fn inflate_gzip(bytes: &[u8], limit: u64) -> Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    GzDecoder::new(bytes)
+        .take(limit + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("invalid gzip data: {}", e))?;
+    if inflated.len() as u64 > limit {
+        Err(format!("gzip data inflates to more than {} bytes", limit))?;
+    }
+    Ok(inflated)
+}
+
+/// Opens `path` for reading, spelling out the usual mistakes: a directory,
+/// a file that doesn't exist, or one the user isn't allowed to read.
+pub(crate) fn open_file(path: &Path) -> Result<File> {
+    if path.is_dir() {
+        Err(format!(
+            "'{}' is a directory, expected a PNG file",
+            path.display()
+        ))?;
+    }
+    File::open(path).map_err(|e| {
+        let reason = match e.kind() {
+            io::ErrorKind::NotFound => "no such file".to_string(),
+            io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+            _ => e.to_string(),
+        };
+        format!("cannot open {}: {}", path.display(), reason).into()
+    })
+}
+
+impl fmt::Display for Png {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PNG header: {:?}", self.header)?;
+
+        for chunk in &self.chunks {
+            writeln!(f, "{}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::convert::TryFrom;
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
+    }
+
+    fn testing_png() -> Png {
+        let chunks = testing_chunks();
+        Png::from_chunks(chunks)
+    }
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+        let data: Vec<u8> = data.bytes().collect();
+
+        Chunk::new(chunk_type, data)
+    }
+
+    #[test]
+    fn test_from_chunks() {
+        let chunks = testing_chunks();
+        let png = Png::from_chunks(chunks);
+
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_valid_from_bytes() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .chain(chunk_bytes.iter())
+            .copied()
+            .collect();
+
+        let png = Png::try_from(bytes.as_slice());
+
+        assert!(png.is_ok());
+    }
+
+    #[test]
+    fn test_chunk_stream_without_signature() {
+        let bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let err = Png::from_bytes(&bytes).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&PngError::InvalidSignature));
+
+        let options = ParseOptions {
+            expect_signature: false,
+            ..Default::default()
+        };
+        let png = Png::from_bytes_with(&bytes, &options).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.header(), &Png::STANDARD_HEADER);
+        assert_eq!(png.as_bytes()[8..], bytes[..]);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let bytes: Vec<u8> = [13, 80, 78, 71, 13, 10, 26, 10]
+            .iter()
+            .chain(chunk_bytes.iter())
+            .copied()
+            .collect();
+
+        let png = Png::try_from(bytes.as_slice());
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk() {
+        let mut chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        #[rustfmt::skip]
+        let mut bad_chunk = vec![
+            0, 0, 0, 5,         // length
+            32, 117, 83, 116,   // Chunk Type (bad)
+            65, 64, 65, 66, 67, // Data
+            1, 2, 3, 4, 5       // CRC (bad)
+        ];
+
+        chunk_bytes.append(&mut bad_chunk);
+
+        let png = Png::try_from(chunk_bytes.as_slice());
+
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_parse_chunks() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let chunks = Png::parse_chunks(&chunk_bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(&chunks[2].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_index_matches_full_parse() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = Png::index(&PNG_FILE, &ParseOptions::default()).unwrap();
+        assert!(index.len() > 3);
+
+        let parsed: Vec<(ChunkType, usize, u32)> = png
+            .chunks()
+            .iter()
+            .zip(png.chunk_offsets())
+            .map(|(chunk, offset)| (chunk.chunk_type().clone(), offset, chunk.length()))
+            .collect();
+        assert_eq!(index, parsed);
+
+        let mut bytes = PNG_FILE.to_vec();
+        let (_, offset, length) = &index[1];
+        bytes[offset + 8 + *length as usize] ^= 0xff;
+        let err = Png::index(&bytes, &ParseOptions::default()).unwrap_err();
+        let expected = format!("chunk parse error at offset {:#x}: CRC mismatch", offset);
+        assert_eq!(err.to_string(), expected);
+        assert_eq!(Png::index(&bytes, &ParseOptions::lenient()).unwrap(), index);
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let tiny_chunk = chunk_from_strings("tiNy", "").unwrap().as_bytes();
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .chain(tiny_chunk.iter().cycle().take(tiny_chunk.len() * 1000))
+            .copied()
+            .collect();
+
+        let options = ParseOptions {
+            max_chunks: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            Png::from_bytes_with(&bytes, &options)
+                .unwrap()
+                .chunks()
+                .len(),
+            1000
+        );
+
+        let options = ParseOptions {
+            max_chunks: 999,
+            ..Default::default()
+        };
+        let err = Png::from_bytes_with(&bytes, &options).err().unwrap();
+        assert_eq!(err.to_string(), "file has more than 999 chunks");
+    }
+
+    /// Records log messages with the thread they came from, since tests share
+    /// the one global logger but run in parallel.
+    struct CaptureLogger;
+
+    static LOG_RECORDS: std::sync::Mutex<Vec<(std::thread::ThreadId, log::Level, String)>> =
+        std::sync::Mutex::new(Vec::new());
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let thread = std::thread::current().id();
+            let entry = (thread, record.level(), record.args().to_string());
+            LOG_RECORDS.lock().unwrap().push(entry);
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// The messages logged on this thread while running `f`.
+    fn captured_logs(f: impl FnOnce()) -> Vec<(log::Level, String)> {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CaptureLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+
+        f();
+        let thread = std::thread::current().id();
+        let mut records = LOG_RECORDS.lock().unwrap();
+        let (mine, others): (Vec<_>, Vec<_>) =
+            records.drain(..).partition(|(t, _, _)| *t == thread);
+        *records = others;
+        mine.into_iter()
+            .map(|(_, level, message)| (level, message))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_logs_each_chunk() {
+        let bytes = testing_png().as_bytes();
+        let logs = captured_logs(|| {
+            Png::from_bytes(&bytes).unwrap();
+        });
+        assert_eq!(
+            logs,
+            [
+                (
+                    log::Level::Trace,
+                    "FrSt chunk at offset 0x8, 20 bytes".to_string()
+                ),
+                (
+                    log::Level::Trace,
+                    "miDl chunk at offset 0x28, 18 bytes".to_string()
+                ),
+                (
+                    log::Level::Trace,
+                    "LASt chunk at offset 0x46, 19 bytes".to_string()
+                ),
+            ]
+        );
+
+        let mut chunks = testing_chunks();
+        let last = chunks.pop().unwrap().with_corrupt_crc();
+        chunks.push(last);
+        let bytes = Png::from_chunks(chunks).as_bytes();
+        let logs = captured_logs(|| {
+            Png::from_bytes_with(&bytes, &ParseOptions::lenient()).unwrap();
+        });
+        assert_eq!(
+            logs[3],
+            (
+                log::Level::Warn,
+                "LASt chunk at offset 0x46 has a bad CRC".to_string()
+            )
+        );
+
+        let logs = captured_logs(|| assert!(Png::from_bytes(&bytes).is_err()));
+        assert_eq!(logs.last().unwrap().0, log::Level::Error);
+    }
+
+    #[test]
+    fn test_lenient_parsing() {
+        let mut chunks = testing_chunks();
+        let corrupt = chunks.remove(1).with_corrupt_crc();
+        chunks.insert(1, corrupt);
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        assert!(Png::from_bytes(&bytes).is_err());
+
+        let png = Png::from_bytes_with(&bytes, &ParseOptions::lenient()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_recalculate_crcs() {
+        let mut chunks = testing_chunks();
+        let corrupt = chunks.remove(1).with_corrupt_crc();
+        chunks.insert(1, corrupt);
+        let mut png = Png::from_chunks(chunks);
+
+        assert_eq!(png.recalculate_crcs(), 1);
+        assert!(Png::from_bytes(&png.as_bytes()).is_ok());
+        assert_eq!(png.recalculate_crcs(), 0);
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut bytes = framed_png().as_bytes();
+        bytes.extend_from_slice(&[0xAB; 10]);
+
+        let png = Png::from_bytes(&bytes).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.trailing_bytes(), &[0xAB; 10]);
+
+        let png = Png::from_bytes(&framed_png().as_bytes()).unwrap();
+        assert!(png.trailing_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_trailing_bytes_are_written_back() {
+        let mut bytes = framed_png().as_bytes();
+        bytes.extend_from_slice(b"payload");
+
+        let mut png = Png::from_bytes(&bytes).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+        assert_eq!(png.total_size(), bytes.len());
+
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        assert_eq!(png.take_trailing_bytes(), b"payload");
+        assert_eq!(png.as_bytes(), framed_png().as_bytes());
+    }
+
+    #[test]
+    fn test_chunks_after_iend_are_trailing() {
+        let mut png = framed_png();
+        png.append_chunk(chunk_from_strings("ruSt", "late").unwrap());
+
+        let parsed = Png::from_bytes(&png.as_bytes()).unwrap();
+        assert!(parsed.chunk_by_type("ruSt").is_none());
+        assert_eq!(parsed.trailing_bytes().len(), 16);
+    }
+
+    #[test]
+    fn test_duplicate_ihdr() {
+        let mut png = framed_png();
+        png.chunks
+            .insert(1, chunk_from_strings("IHDR", "again").unwrap());
+        let bytes = png.as_bytes();
+
+        let offset = png.chunk_offsets()[1];
+        let err = Png::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("duplicate chunks: IHDR at offset {:#x}", offset)
+        );
+
+        let lenient = Png::from_bytes_with(&bytes, &ParseOptions::lenient()).unwrap();
+        assert_eq!(lenient.duplicate_ihdr_iend(), [("IHDR", offset)]);
+    }
+
+    #[test]
+    fn test_duplicate_iend() {
+        let mut png = framed_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        let bytes = png.as_bytes();
+
+        let offset = bytes.len() - 12;
+        let err = Png::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("duplicate chunks: IEND at offset {:#x}", offset)
+        );
+
+        let lenient = Png::from_bytes_with(&bytes, &ParseOptions::lenient()).unwrap();
+        assert_eq!(lenient.duplicate_ihdr_iend(), [("IEND", offset)]);
+        assert_eq!(lenient.trailing_bytes().len(), 12);
+    }
+
+    #[test]
+    fn test_list_chunks() {
+        let png = testing_png();
+        let chunks = png.chunks();
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_chunks_mut() {
+        let mut png = testing_png();
+        for chunk in png.chunks_mut() {
+            let upper = chunk.data().to_ascii_uppercase();
+            chunk.set_data(upper).unwrap();
+        }
+
+        let parsed = Png::from_bytes(&png.as_bytes()).unwrap();
+        assert_eq!(parsed.as_bytes(), png.as_bytes());
+        assert_eq!(parsed.chunks()[0].data(), b"I AM THE FIRST CHUNK");
+    }
+
+    #[test]
+    fn test_chunk_by_type() {
+        let png = testing_png();
+        let chunk = png.chunk_by_type("FrSt").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("ruSt", "one").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("ruSt", "two").unwrap(),
+            chunk_from_strings("ruSt", "three").unwrap(),
+        ]);
+        let data: Vec<&[u8]> = png.chunks_by_type("ruSt").map(Chunk::data).collect();
+        assert_eq!(data, [&b"one"[..], b"two", b"three"]);
+        assert_eq!(png.chunks_by_type("IDAT").count(), 1);
+        assert_eq!(png.chunks_by_type("no").count(), 0);
+    }
+
+    #[test]
+    fn test_data_of_all() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("ruSt", "one ").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("ruSt", "two ").unwrap(),
+            chunk_from_strings("ruSt", "three").unwrap(),
+        ]);
+        assert_eq!(png.data_of_all("ruSt"), b"one two three");
+        assert!(png.data_of_all("RUST").is_empty());
+        assert!(png.data_of_all("bad").is_empty());
+    }
+
+    #[test]
+    fn test_parse_error_names_offset() {
+        let mut chunks = testing_chunks();
+        let last = chunks.pop().unwrap().with_corrupt_crc();
+        chunks.push(last);
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        // 8 signature bytes, then chunks of 20 and 18 data bytes plus 12 each
+        let err = Png::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "chunk parse error at offset 0x46: CRC mismatch"
+        );
+    }
+
+    #[test]
+    fn test_recover_skips_garbage() {
+        let chunks = testing_chunks();
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend(chunks[0].as_bytes());
+        bytes.extend(b"\xff\xff\xff\xffgarbage that is not a chunk");
+        bytes.extend(chunks[1].as_bytes());
+        bytes.extend(chunks[2].as_bytes());
+
+        let err = Png::from_bytes(&bytes).err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("chunk parse error at offset 0x28: "));
+
+        let options = ParseOptions {
+            recover: true,
+            ..Default::default()
+        };
+        let png = Png::from_bytes_with(&bytes, &options).unwrap();
+        assert_eq!(png.chunks(), &chunks[..]);
+        assert_eq!(png.skipped_regions(), [(0x28, 31)]);
+        assert!(Png::from_bytes(&bytes).is_err());
+        assert!(Png::from_bytes(&png.as_bytes())
+            .unwrap()
+            .skipped_regions()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_recover_scans_a_limited_distance() {
+        let chunks = testing_chunks();
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend(chunks[0].as_bytes());
+        let garbage = 0x28..0x28 + Png::RECOVERY_SCAN_LIMIT + 1;
+        bytes.resize(garbage.end, 0xff);
+        bytes.extend(chunks[1].as_bytes());
+
+        let options = ParseOptions {
+            recover: true,
+            ..Default::default()
+        };
+        let png = Png::from_bytes_with(&bytes, &options).unwrap();
+        assert_eq!(png.chunks(), &chunks[..1]);
+        assert_eq!(png.skipped_regions(), [(0x28, bytes.len() - 0x28)]);
+
+        bytes.drain(0x29..garbage.end);
+        let png = Png::from_bytes_with(&bytes, &options).unwrap();
+        assert_eq!(png.chunks(), &chunks[..2]);
+    }
+
+    #[test]
+    fn test_default_is_minimal_valid_image() {
+        let png = Png::default();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!((ihdr.width, ihdr.height, ihdr.bit_depth), (1, 1, 8));
+        assert_eq!(ihdr.color_type_name(), Some("greyscale"));
+
+        let mut scanline = Vec::new();
+        flate2::read::ZlibDecoder::new(png.chunks()[1].data())
+            .read_to_end(&mut scanline)
+            .unwrap();
+        assert_eq!(scanline, [0, 0]);
+
+        let reparsed = Png::from_bytes(&png.as_bytes()).unwrap();
+        assert_eq!(reparsed.as_bytes(), png.as_bytes());
+        assert!(reparsed.duplicate_ihdr_iend().is_empty());
+        assert!(reparsed.palette_transparency_problem().is_none());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let png = testing_png();
+        assert_eq!(png.chunk_offsets(), [8, 40, 70]);
+
+        let bytes = png.as_bytes();
+        for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+            assert_eq!(&bytes[offset + 4..offset + 8], chunk.chunk_type().bytes());
+        }
+    }
+
+    #[test]
+    fn test_from_gzip_compressed() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let png = testing_png();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&png.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let parsed = Png::from_reader(gzipped.as_slice()).unwrap();
+        assert_eq!(parsed.as_bytes(), png.as_bytes());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("image.png.gz");
+        std::fs::write(&path, &gzipped).unwrap();
+        assert_eq!(Png::from_file(&path).unwrap().as_bytes(), png.as_bytes());
+
+        let err = Png::from_reader(&gzipped[..gzipped.len() / 2])
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("invalid gzip data"));
+
+        let size = png.total_size() as u64;
+        assert_eq!(inflate_gzip(&gzipped, size).unwrap(), png.as_bytes());
+        let err = inflate_gzip(&gzipped, size - 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("gzip data inflates to more than {} bytes", size - 1)
+        );
+    }
+
+    #[test]
+    fn test_len_and_iteration() {
+        let png = Png::from_bytes(&testing_png().as_bytes()).unwrap();
+        assert_eq!(png.len(), 3);
+        assert_eq!(png.chunk_count(), 3);
+        assert!(!png.is_empty());
+
+        let mut lengths = Vec::new();
+        for chunk in &png {
+            lengths.push(chunk.length());
         }
+        assert_eq!(lengths, [20, 18, 19]);
+        assert_eq!((&png).into_iter().count(), png.len());
+
+        assert!(Png::from_chunks(vec![]).is_empty());
     }
 
-    pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+    #[test]
+    fn test_index_and_as_ref() {
+        let png = testing_png();
+        assert_eq!(&png[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(png[2].data(), png.chunks()[2].data());
+
+        let chunks: &[Chunk] = png.as_ref();
+        assert_eq!(chunks.len(), png.len());
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let index = self.get_index_for_type(chunk_type)?;
-        Ok(self.chunks.remove(index))
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_index_out_of_range_panics() {
+        let png = testing_png();
+        let _ = &png[png.len()];
     }
 
-    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        match self.get_index_for_type(chunk_type) {
-            Ok(index) => Some(&self.chunks[index]),
-            Err(_) => None,
-        }
+    #[test]
+    fn test_append_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let chunk = png.chunk_by_type("TeSt").unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
+        assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    fn framed_png() -> Png {
+        Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ])
+    }
 
-        bytes.extend_from_slice(&self.header);
+    #[test]
+    fn test_insert_chunk_at_start() {
+        let mut png = framed_png();
+        let chunk = chunk_from_strings("gAMA", "gamma").unwrap();
+        assert!(png.insert_chunk_at(0, chunk).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
 
-        for chunk in &self.chunks {
-            bytes.extend_from_slice(&Chunk::as_bytes(chunk));
-        }
-        bytes
+    #[test]
+    fn test_insert_chunk_at_middle() {
+        let mut png = framed_png();
+        png.insert_chunk_at(1, chunk_from_strings("gAMA", "gamma").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "gAMA");
+        assert_eq!(&png.chunks()[2].chunk_type().to_string(), "IDAT");
     }
 
-    pub fn header(&self) -> &[u8; 8] {
-        &self.header
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = framed_png();
+        png.insert_chunk_at(2, chunk_from_strings("ruSt", "message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[2].chunk_type().to_string(), "ruSt");
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "IEND");
+
+        let chunk = chunk_from_strings("ruSt", "late").unwrap();
+        assert!(png.insert_chunk_at(4, chunk).is_err());
+        let chunk = chunk_from_strings("ruSt", "late").unwrap();
+        assert!(png.insert_chunk_at(9, chunk).is_err());
     }
 
-    pub fn chunks(&self) -> &[Chunk] {
-        &self.chunks
+    #[test]
+    fn test_insert_after_type() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("PLTE", "palette").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("IDAT", "more pixels").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.insert_after_type("PLTE", chunk_from_strings("tRNS", "alpha").unwrap())
+            .unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "PLTE", "tRNS", "IDAT", "IDAT", "IEND"]
+        );
+
+        png.insert_after_type("IDAT", chunk_from_strings("ruSt", "late").unwrap())
+            .unwrap();
+        assert_eq!(chunk_types(&png)[5], "ruSt");
+
+        let err = png.insert_after_type("gAMA", chunk_from_strings("ruSt", "x").unwrap());
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "no gAMA chunk to insert after"
+        );
+        assert!(png
+            .insert_after_type("IEND", chunk_from_strings("ruSt", "x").unwrap())
+            .is_err());
+        assert_eq!(png.chunks().len(), 7);
     }
 
-    fn get_index_for_type(&self, chunk_type: &str) -> Result<usize> {
-        let chunk_type = ChunkType::from_str(chunk_type)?;
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("tEXt", "Comment").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("ruSt", "secret").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
 
-        let index = self.chunks
-            .iter()
-            .position(|chunk| chunk.chunk_type() == &chunk_type)
-            .ok_or("chunk not found")?;
+        let removed = png.strip_ancillary(&[ChunkType::from_str("gAMA").unwrap()]);
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(removed, ["tEXt", "ruSt"]);
 
-        Ok(index)
+        let kept: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(kept, ["IHDR", "gAMA", "IDAT", "IEND"]);
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
-    fn try_from(input_bytes: &[u8]) -> Result<Self> {
-        let mut chunks = Vec::new();
+    #[test]
+    fn test_encode() {
+        let mut png = framed_png();
+        png.encode("ruSt", "hidden message").unwrap();
 
-        // Split of header from the rest of the bytes
-        let header: [u8; 8] = input_bytes[0..8].try_into()?;
-        let mut bytes = input_bytes.to_vec();
-        bytes.drain(0..8);
+        assert_eq!(&png.chunks()[2].chunk_type().to_string(), "ruSt");
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "IEND");
 
-        if header != Png::STANDARD_HEADER {
-            Err("invalid header")?;
-        }
+        let parsed = Png::from_bytes(&png.as_bytes()).unwrap();
+        let chunk = parsed.chunk_by_type("ruSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "hidden message");
+    }
 
-        // Create chunks until there are no more bytes
-        while bytes.len() > 0 {
-            let chunk = Chunk::try_from(&bytes)?;
-            let range = 0..chunk.length() as usize + 12;
-            
-            chunks.push(chunk);
-            bytes.drain(range);
-        }
+    #[test]
+    fn test_decode() {
+        let mut png = framed_png();
+        png.encode("ruSt", "hidden message").unwrap();
+        assert_eq!(
+            png.decode("ruSt").unwrap().as_deref(),
+            Some("hidden message")
+        );
+        assert_eq!(png.decode("miSs").unwrap(), None);
+        assert!(png.decode("ru5t").is_err());
 
-        Ok(Png { header, chunks })
+        let chunk_type = ChunkType::from_str("biNy").unwrap();
+        png.append_chunk(Chunk::new(chunk_type, vec![0xFF, 0xFE]).unwrap());
+        assert!(png.decode("biNy").is_err());
     }
-}
 
-impl fmt::Display for Png {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "PNG header: {:?}", self.header)?;
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = framed_png();
+        png.encode("ruSt", "old message").unwrap();
+        png.encode("ruSt", "second message").unwrap();
 
-        for chunk in &self.chunks {
-            writeln!(f, "{}", chunk)?;
-        }
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::new(chunk_type.clone(), b"new message".to_vec()).unwrap();
+        assert!(png.replace_chunk("ruSt", chunk).unwrap());
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "ruSt", "IEND"]);
+        assert_eq!(png.chunks()[2].data(), b"new message");
+        assert_eq!(png.chunks()[3].data(), b"second message");
 
-        Ok(())
+        let chunk = Chunk::new(chunk_type, vec![]).unwrap();
+        assert!(!png.replace_chunk("miSs", chunk).unwrap());
+        assert_eq!(png.chunks().len(), 5);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::chunk_type::ChunkType;
-    use crate::chunk::Chunk;
-    use std::convert::TryFrom;
+    #[test]
+    fn test_append_to_chunk() {
+        let mut png = framed_png();
+        png.encode("ruSt", "first").unwrap();
+        png.encode("ruSt", "second").unwrap();
 
-    fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
+        assert!(png.append_to_chunk("ruSt", b" part").unwrap());
+        let chunk = &png.chunks()[2];
+        assert_eq!(chunk.data(), b"first part");
+        assert_eq!(chunk.length(), 10);
+        assert_eq!(
+            chunk.crc(),
+            Chunk::crc_of(chunk.chunk_type(), b"first part")
+        );
+        assert_eq!(png.chunks()[3].data(), b"second");
+
+        assert!(!png.append_to_chunk("miSs", b"x").unwrap());
+    }
 
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
+    #[test]
+    fn test_encode_without_iend() {
+        let mut png = testing_png();
+        png.encode("ruSt", "hidden message").unwrap();
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "ruSt");
 
-        chunks
+        assert!(png.encode("ru5t", "hidden message").is_err());
+        assert_eq!(png.chunks().len(), 4);
     }
 
-    fn testing_png() -> Png {
-        let chunks = testing_chunks();
-        Png::from_chunks(chunks)
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
     }
 
-    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
-        let chunk_type = ChunkType::from_str(chunk_type)?;
-        let data: Vec<u8> = data.bytes().collect();
+    #[test]
+    fn test_reorder_canonical() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("PLTE", "palette").unwrap(),
+            chunk_from_strings("ruSt", "first").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("tRNS", "alpha").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("tEXt", "Comment").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
 
-        Ok(Chunk::new(chunk_type, data))
+        png.reorder_canonical();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "gAMA", "PLTE", "ruSt", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]
+        );
+        assert!(Png::from_bytes(&png.as_bytes()).is_ok());
     }
 
     #[test]
-    fn test_from_chunks() {
-        let chunks = testing_chunks();
-        let png = Png::from_chunks(chunks);
+    fn test_reorder_canonical_ignores_free_chunk_order() {
+        let reordered = |types: [(&str, &str); 7]| {
+            let chunks = types
+                .iter()
+                .map(|(t, data)| chunk_from_strings(t, data).unwrap())
+                .collect();
+            let mut png = Png::from_chunks(chunks);
+            png.reorder_canonical();
+            png.as_bytes()
+        };
 
-        assert_eq!(png.chunks().len(), 3);
+        let canonical = reordered([
+            ("IHDR", ""),
+            ("ruSt", "b"),
+            ("tEXt", "A"),
+            ("ruSt", "a"),
+            ("IDAT", ""),
+            ("tEXt", "B"),
+            ("IEND", ""),
+        ]);
+        let swapped = reordered([
+            ("IHDR", ""),
+            ("ruSt", "a"),
+            ("ruSt", "b"),
+            ("IDAT", ""),
+            ("tEXt", "B"),
+            ("tEXt", "A"),
+            ("IEND", ""),
+        ]);
+        assert_eq!(canonical, swapped);
+
+        let png = Png::from_bytes(&canonical).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "ruSt", "ruSt", "IDAT", "tEXt", "tEXt", "IEND"]
+        );
+        assert_eq!(png.chunks()[1].data(), b"a");
+        assert_eq!(png.chunks()[4].data(), b"A");
     }
 
     #[test]
-    fn test_valid_from_bytes() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
-            .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
-
-        let bytes: Vec<u8> = Png::STANDARD_HEADER
+    fn test_reorder_canonical_keeps_valid_order() {
+        let types = ["IHDR", "sRGB", "PLTE", "bKGD", "tRNS", "IDAT", "IEND"];
+        let chunks = types
             .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
+            .map(|t| chunk_from_strings(t, "").unwrap())
             .collect();
+        let mut png = Png::from_chunks(chunks);
 
-        let png = Png::try_from(bytes.as_ref());
+        png.reorder_canonical();
+        assert_eq!(chunk_types(&png), types);
+    }
 
-        assert!(png.is_ok());
+    #[test]
+    fn test_remove_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.remove_chunk("TeSt").unwrap();
+        let chunk = png.chunk_by_type("TeSt");
+        assert!(chunk.is_none());
     }
 
     #[test]
-    fn test_invalid_header() {
-        let chunk_bytes: Vec<u8> = testing_chunks()
-            .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
+    fn test_ihdr() {
+        // 640x480, 8-bit truecolour with alpha, Adam7 interlaced
+        let data = [0, 0, 2, 128, 0, 0, 1, 224, 8, 6, 0, 0, 1];
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), data.to_vec()).unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
 
-        let bytes: Vec<u8> = [13, 80, 78, 71, 13, 10, 26, 10]
-            .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
-            .collect();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!(ihdr.width, 640);
+        assert_eq!(ihdr.height, 480);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, 6);
+        assert_eq!(ihdr.compression_method, 0);
+        assert_eq!(ihdr.filter_method, 0);
+        assert_eq!(ihdr.interlace_method, 1);
+    }
 
-        let png = Png::try_from(bytes.as_ref());
+    #[test]
+    fn test_time() {
+        let time = Time::try_from(&[0x07, 0xE8, 2, 29, 23, 5, 9][..]).unwrap();
+        assert_eq!(time.to_string(), "2024-02-29T23:05:09Z");
 
-        assert!(png.is_err());
+        let mut png = framed_png();
+        assert!(png.modification_time().is_none());
+        let chunk_type = ChunkType::from_str("tIME").unwrap();
+        png.insert_before_iend(Chunk::new(chunk_type, vec![0x07, 0xE8, 2, 29, 23, 5, 9]).unwrap());
+        assert_eq!(png.modification_time().unwrap().unwrap(), time);
     }
 
     #[test]
-    fn test_invalid_chunk() {
-        let mut chunk_bytes: Vec<u8> = testing_chunks()
-            .into_iter()
-            .flat_map(|chunk| chunk.as_bytes())
-            .collect();
+    fn test_time_malformed() {
+        let err = Time::try_from(&[0x07, 0xE8, 13, 1, 0, 0, 0][..]).unwrap_err();
+        assert_eq!(err.to_string(), "tIME month 13 is outside 1-12");
 
-        #[rustfmt::skip]
-        let mut bad_chunk = vec![
-            0, 0, 0, 5,         // length
-            32, 117, 83, 116,   // Chunk Type (bad)
-            65, 64, 65, 66, 67, // Data
-            1, 2, 3, 4, 5       // CRC (bad)
-        ];
+        let err = Time::try_from(&[0x07, 0xE8, 1, 1, 24, 0, 0][..]).unwrap_err();
+        assert_eq!(err.to_string(), "tIME hour 24 is outside 0-23");
 
-        chunk_bytes.append(&mut bad_chunk);
+        assert!(Time::try_from(&[0x07, 0xE8, 1, 1, 0, 0][..]).is_err());
+    }
 
-        let png = Png::try_from(chunk_bytes.as_ref());
+    #[test]
+    fn test_ihdr_names() {
+        let data = [0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 1];
+        let ihdr = Ihdr::try_from(&data[..]).unwrap();
+        assert_eq!(ihdr.color_type_name(), Some("RGBA"));
+        assert_eq!(ihdr.interlace_name(), Some("Adam7"));
 
-        assert!(png.is_err());
+        let data = [0, 0, 0, 1, 0, 0, 0, 1, 8, 5, 0, 0, 2];
+        let ihdr = Ihdr::try_from(&data[..]).unwrap();
+        assert_eq!(ihdr.color_type_name(), None);
+        assert_eq!(ihdr.interlace_name(), None);
     }
 
+    #[test]
+    fn test_ihdr_missing_or_malformed() {
+        assert!(testing_png().ihdr().is_err());
+
+        let png = Png::from_chunks(vec![chunk_from_strings("IHDR", "short").unwrap()]);
+        assert_eq!(
+            png.ihdr().unwrap_err().to_string(),
+            "IHDR data must be 13 bytes, got 5"
+        );
+    }
 
     #[test]
-    fn test_list_chunks() {
-        let png = testing_png();
-        let chunks = png.chunks();
-        assert_eq!(chunks.len(), 3);
+    fn test_ihdr_from_image_file() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!(ihdr.bit_depth, 8);
+        assert!(ihdr.width > 0 && ihdr.height > 0);
     }
 
     #[test]
-    fn test_chunk_by_type() {
-        let png = testing_png();
-        let chunk = png.chunk_by_type("FrSt").unwrap();
-        assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
-        assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
+    fn test_remove_chunks_of_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "two").unwrap());
 
+        let removed = png.remove_chunks_of_type(&ChunkType::from_str("TeSt").unwrap());
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data(), b"two");
+        assert_eq!(png.chunks().len(), 3);
+
+        let removed = png.remove_chunks_of_type(&ChunkType::from_str("TeSt").unwrap());
+        assert!(removed.is_empty());
     }
 
     #[test]
-    fn test_append_chunk() {
-        let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        let chunk = png.chunk_by_type("TeSt").unwrap();
-        assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
-        assert_eq!(&chunk.data_as_string().unwrap(), "Message");
+    fn test_remove_chunks_where() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("tEXt", "Comment").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("ruSt", "secret").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        assert_eq!(png.remove_chunks_where(|chunk| chunk.is_ancillary()), 2);
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(png.remove_chunks_where(|chunk| chunk.is_ancillary()), 0);
     }
 
     #[test]
-    fn test_remove_chunk() {
+    fn test_relabel_chunks() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        png.remove_chunk("TeSt").unwrap();
-        let chunk = png.chunk_by_type("TeSt");
-        assert!(chunk.is_none());
+        png.append_chunk(chunk_from_strings("TeSt", "one").unwrap());
+        let from = ChunkType::from_str("TeSt").unwrap();
+        let to = ChunkType::from_str("myTx").unwrap();
+
+        assert_eq!(png.relabel_chunks(&from, &to), 1);
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert_eq!(png.chunk_by_type("myTx").unwrap().data(), b"one");
+        assert_eq!(png.relabel_chunks(&from, &to), 0);
+    }
+
+    #[test]
+    fn test_hidden_chunks() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("tEXt", "Comment").unwrap(),
+            chunk_from_strings("ruSt", "secret").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let hidden = png.hidden_chunks();
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(&hidden[0].chunk_type().to_string(), "ruSt");
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        // 1x1 truecolour, 8 bits per sample
+        let ihdr = Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0],
+        )
+        .unwrap();
+        let sbit = Chunk::new(ChunkType::from_str("sBIT").unwrap(), vec![5, 6, 5]).unwrap();
+        let png = Png::from_chunks(vec![ihdr, sbit]);
+        assert_eq!(png.significant_bits(), Some(vec![5, 6, 5]));
+
+        let png = testing_png();
+        assert_eq!(png.significant_bits(), None);
+    }
+
+    #[test]
+    fn test_significant_bits_wrong_length() {
+        // 1x1 greyscale expects a single byte
+        let ihdr = Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+        )
+        .unwrap();
+        let sbit = Chunk::new(ChunkType::from_str("sBIT").unwrap(), vec![5, 6, 5]).unwrap();
+        let png = Png::from_chunks(vec![ihdr, sbit]);
+        assert_eq!(png.significant_bits(), None);
+    }
+
+    #[test]
+    fn test_palette_transparency_problem() {
+        // 1x1 indexed colour with a two-entry palette
+        let ihdr = || {
+            Chunk::new(
+                ChunkType::from_str("IHDR").unwrap(),
+                vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0],
+            )
+            .unwrap()
+        };
+        let plte = || Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0; 6]).unwrap();
+        let trns = |len| Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![255; len]).unwrap();
+
+        let png = Png::from_chunks(vec![ihdr(), plte(), trns(2)]);
+        assert_eq!(png.palette_transparency_problem(), None);
+
+        let png = Png::from_chunks(vec![ihdr(), plte(), trns(3)]);
+        assert_eq!(
+            png.palette_transparency_problem().unwrap(),
+            "tRNS chunk has 3 alpha entries but PLTE has only 2 palette entries"
+        );
     }
 
     #[test]
@@ -241,14 +2115,159 @@ mod tests {
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_from_reader() {
+        let png = Png::from_reader(std::io::Cursor::new(&PNG_FILE[..])).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+
+        assert!(Png::from_reader(std::io::Cursor::new(&PNG_FILE[..20])).is_err());
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("dice.png");
+        std::fs::write(&path, PNG_FILE).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(
+            png.chunks().len(),
+            Png::try_from(&PNG_FILE[..]).unwrap().chunks().len()
+        );
+
+        assert!(Png::from_file(dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_from_file_directory_or_missing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let err = Png::from_file(dir.path()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "'{}' is a directory, expected a PNG file",
+                dir.path().display()
+            )
+        );
+
+        let missing = dir.path().join("missing.png");
+        let err = Png::from_file(&missing).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("cannot open {}: no such file", missing.display())
+        );
+    }
+
+    #[test]
+    fn test_try_from_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("dice.png");
+        std::fs::write(&path, PNG_FILE).unwrap();
+
+        let png: Png = path.to_str().unwrap().try_into().unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+
+        let missing = dir.path().join("missing.png");
+        let err = Png::try_from(missing.to_str().unwrap()).err().unwrap();
+        assert!(err.to_string().starts_with("cannot open "));
+
+        std::fs::write(&path, b"not a png").unwrap();
+        let err = Png::try_from(path.to_str().unwrap()).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&PngError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let png = testing_png();
+        let mut bytes = Vec::new();
+        png.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, png.as_bytes());
+    }
+
+    #[test]
+    fn test_write_to_buffers_small_writes() {
+        struct CountingWriter(Vec<u8>, usize);
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1 += 1;
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut png = testing_png();
+        let text_type = ChunkType::from_str("teXt").unwrap();
+        for _ in 0..1000 {
+            png.insert_before_iend(Chunk::new(text_type.clone(), vec![7; 20]).unwrap());
+        }
+        let mut writer = CountingWriter(Vec::new(), 0);
+        png.write_to(&mut writer).unwrap();
+        assert_eq!(writer.0, png.as_bytes());
+        assert!(writer.1 < 5, "{} writes", writer.1);
+    }
+
+    #[test]
+    fn test_write_large_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("large.png");
+
+        let mut png = testing_png();
+        let data: Vec<u8> = (0..3 * WRITE_BUFFER_SIZE).map(|i| i as u8).collect();
+        let idat_type = ChunkType::from_str("IDAT").unwrap();
+        for _ in 0..4 {
+            png.insert_before_iend(Chunk::new(idat_type.clone(), data.clone()).unwrap());
+        }
+        png.write_file(&path).unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(written.len(), png.total_size());
+        assert_eq!(written, png.as_bytes());
+    }
+
+    #[test]
+    fn test_write_to_with_progress() {
+        let png = testing_png();
+        let mut bytes = Vec::new();
+        let mut seen = Vec::new();
+        png.write_to_with_progress(&mut bytes, |n| seen.push(n))
+            .unwrap();
+
+        assert_eq!(seen.len(), 1 + png.chunks().len());
+        assert!(seen.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(seen.last(), Some(&(bytes.len() as u64)));
+    }
+
+    #[test]
+    fn test_write_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("written.png");
+
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.write_file(&path).unwrap();
+        assert_eq!(Png::from_file(&path).unwrap().as_bytes(), png.as_bytes());
+        assert_eq!(std::fs::read(&path).unwrap(), PNG_FILE);
+    }
+
     #[test]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_total_size() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.total_size(), PNG_FILE.len());
+        assert_eq!(png.as_bytes().len(), png.total_size());
+
+        let png = testing_png();
+        assert_eq!(png.as_bytes().len(), png.total_size());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -262,7 +2281,7 @@ mod tests {
             .copied()
             .collect();
 
-        let png: Png = TryFrom::try_from(bytes.as_ref()).unwrap();
+        let png: Png = TryFrom::try_from(bytes.as_slice()).unwrap();
 
         let _png_string = format!("{}", png);
     }
@@ -513,4 +2532,4 @@ mod tests {
         202, 28, 31, 66, 176, 235, 16, 0, 0, 0, 3, 82, 117, 83, 116, 104, 101, 121, 158, 176, 245,
         160, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
     ];
-}
\ No newline at end of file
+}
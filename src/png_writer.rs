@@ -0,0 +1,89 @@
+use std::io::Write;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+const IEND: ChunkType = ChunkType::from_ascii(b"IEND");
+
+/// Writes a PNG one chunk at a time instead of building it in memory first,
+/// the writing counterpart of `ChunkReader`.
+///
+/// The signature is written on construction; chunks go straight to the
+/// underlying writer in the order they are given.
+pub struct PngWriter<W: Write> {
+    writer: W,
+    wrote_iend: bool,
+}
+
+impl<W: Write> PngWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+        Ok(PngWriter {
+            writer,
+            wrote_iend: false,
+        })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        self.writer.write_all(&chunk.as_bytes())?;
+        self.wrote_iend |= chunk.chunk_type() == &IEND;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer. With `add_iend`, an empty
+    /// `IEND` chunk is written first unless one has been already.
+    pub fn finish(mut self, add_iend: bool) -> Result<W> {
+        if add_iend && !self.wrote_iend {
+            self.write_chunk(&Chunk::empty(IEND))?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_reader::ChunkReader;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let chunks = [
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", b"hi"),
+            chunk("IDAT", &[1, 2]),
+        ];
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        for chunk in &chunks {
+            writer.write_chunk(chunk).unwrap();
+        }
+        let bytes = writer.finish(true).unwrap();
+
+        let read: Vec<Chunk> = ChunkReader::from_png(Cursor::new(&bytes))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read[..3], chunks);
+        assert_eq!(read[3], chunk("IEND", &[]));
+        assert!(Png::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_finish_adds_iend_only_once() {
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer.write_chunk(&chunk("IEND", &[])).unwrap();
+        let bytes = writer.finish(true).unwrap();
+        assert_eq!(bytes.len(), Png::STANDARD_HEADER.len() + 12);
+
+        let bytes = PngWriter::new(Vec::new()).unwrap().finish(false).unwrap();
+        assert_eq!(bytes, Png::STANDARD_HEADER);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::Result;
+
+/// Bytes at the start of every part: its index, then the number of parts,
+/// both as big-endian `u32`s.
+pub const PART_HEADER_LEN: usize = 8;
+
+/// Splits `payload` across chunks of `chunk_type`, each holding at most
+/// `max_len` bytes of data including the part header.
+///
+/// The type must be ancillary, since decoders would otherwise refuse the
+/// file on meeting an unknown critical chunk.
+pub fn split_payload(chunk_type: &ChunkType, payload: &[u8], max_len: usize) -> Result<Vec<Chunk>> {
+    if chunk_type.is_critical() {
+        Err(format!(
+            "chunk type '{}' is critical; split payloads need an ancillary type",
+            chunk_type
+        ))?;
+    }
+    if max_len <= PART_HEADER_LEN {
+        Err(format!(
+            "split size must be more than the {}-byte part header",
+            PART_HEADER_LEN
+        ))?;
+    }
+
+    let pieces: Vec<&[u8]> = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(max_len - PART_HEADER_LEN).collect()
+    };
+    let count = u32::try_from(pieces.len()).map_err(|_| "payload needs too many parts")?;
+
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, piece)| {
+            let mut data = Vec::with_capacity(PART_HEADER_LEN + piece.len());
+            data.extend_from_slice(&(index as u32).to_be_bytes());
+            data.extend_from_slice(&count.to_be_bytes());
+            data.extend_from_slice(piece);
+            Chunk::new(chunk_type.clone(), data)
+        })
+        .collect()
+}
+
+/// Reassembles a payload from the parts written by `split_payload`, in
+/// whatever order they appear.
+pub fn join_parts<'a>(parts: impl IntoIterator<Item = &'a Chunk>) -> Result<Vec<u8>> {
+    let mut indexed = Vec::new();
+    for part in parts {
+        let data = part.data();
+        if data.len() < PART_HEADER_LEN {
+            Err(format!(
+                "{} chunk is too short to hold a part header",
+                part.chunk_type()
+            ))?;
+        }
+        let index = u32::from_be_bytes(data[0..4].try_into()?);
+        let count = u32::from_be_bytes(data[4..8].try_into()?);
+        indexed.push((index, count, &data[PART_HEADER_LEN..]));
+    }
+
+    let count = match indexed.first() {
+        Some((_, count, _)) => *count,
+        None => Err("no parts to join")?,
+    };
+    if indexed.iter().any(|(_, c, _)| *c != count) {
+        Err("parts disagree on how many parts there are")?;
+    }
+
+    indexed.sort_by_key(|(index, _, _)| *index);
+    let indexes: Vec<u32> = indexed.iter().map(|(index, _, _)| *index).collect();
+    // The count comes from the file, so it is never used to size anything
+    let complete = indexes.len() == count as usize
+        && indexes
+            .iter()
+            .enumerate()
+            .all(|(i, index)| *index as usize == i);
+    if !complete {
+        Err(format!(
+            "expected parts 0 to {}, found {:?}",
+            count.saturating_sub(1),
+            indexes
+        ))?;
+    }
+
+    Ok(indexed
+        .into_iter()
+        .flat_map(|(_, _, piece)| piece.iter().copied())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_split_and_join_three_parts() {
+        let payload: Vec<u8> = (0..50).collect();
+        let mut parts = split_payload(&chunk_type(), &payload, 28).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.data().len() <= 28));
+
+        parts.swap(0, 2);
+        assert_eq!(join_parts(&parts).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_join_missing_part() {
+        let payload: Vec<u8> = (0..50).collect();
+        let mut parts = split_payload(&chunk_type(), &payload, 28).unwrap();
+        parts.remove(1);
+
+        let err = join_parts(&parts).unwrap_err();
+        assert_eq!(err.to_string(), "expected parts 0 to 2, found [0, 2]");
+    }
+
+    #[test]
+    fn test_join_huge_count() {
+        let mut data = vec![0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        data.extend_from_slice(b"piece");
+        let part = Chunk::new(chunk_type(), data).unwrap();
+
+        let err = join_parts(&[part]).unwrap_err();
+        assert_eq!(err.to_string(), "expected parts 0 to 4294967294, found [0]");
+    }
+
+    #[test]
+    fn test_split_rejects_critical_type_and_tiny_size() {
+        let critical = ChunkType::from_str("RuSt").unwrap();
+        assert!(split_payload(&critical, b"hello", 100).is_err());
+        assert!(split_payload(&chunk_type(), b"hello", PART_HEADER_LEN).is_err());
+    }
+}
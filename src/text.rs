@@ -0,0 +1,394 @@
+use std::io::{Read, Write};
+use std::str;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::Result;
+
+/// The only compression method defined by the PNG specification: zlib deflate.
+pub const COMPRESSION_DEFLATE: u8 = 0;
+
+/// The most bytes compressed text may inflate to, so a small chunk can't
+/// expand into gigabytes.
+pub const MAX_INFLATED_TEXT: u64 = 16 * 1024 * 1024;
+
+/// Builds compressed text chunk data in the `zTXt` layout: the keyword, a
+/// null separator, the compression method byte, then the zlib-compressed text.
+///
+/// The text is always deflated; `method` is only written into the layout, so
+/// non-zero values can be used to produce files for compatibility testing.
+pub fn compress_text(keyword: &str, text: &[u8], method: u8) -> Result<Vec<u8>> {
+    let mut data = encode_keyword(keyword)?;
+    data.push(0);
+    data.push(method);
+
+    let mut encoder = ZlibEncoder::new(data, Compression::default());
+    encoder.write_all(text)?;
+    Ok(encoder.finish()?)
+}
+
+/// Splits `zTXt`-layout data into its keyword and inflated text, rejecting
+/// compression methods other than deflate.
+pub fn decompress_text(data: &[u8]) -> Result<(String, Vec<u8>)> {
+    let separator = data
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or("compressed text has no keyword separator")?;
+    let keyword = latin1_to_string(&data[..separator]);
+
+    let method = *data
+        .get(separator + 1)
+        .ok_or("compressed text has no compression method")?;
+    if method != COMPRESSION_DEFLATE {
+        Err(format!("unsupported compression method {}", method))?;
+    }
+
+    Ok((keyword, inflate_text(&data[separator + 2..])?))
+}
+
+/// Inflates zlib-compressed text, failing once it grows past
+/// [`MAX_INFLATED_TEXT`].
+fn inflate_text(compressed: &[u8]) -> Result<Vec<u8>> {
+    let mut text = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(MAX_INFLATED_TEXT + 1)
+        .read_to_end(&mut text)
+        .map_err(|e| format!("invalid compressed text: {}", e))?;
+    if text.len() as u64 > MAX_INFLATED_TEXT {
+        Err(format!(
+            "compressed text inflates to more than {} bytes",
+            MAX_INFLATED_TEXT
+        ))?;
+    }
+    Ok(text)
+}
+
+/// Splits `tEXt` data on its first null byte into the keyword and the text,
+/// both decoded as Latin-1.
+pub fn split_text(data: &[u8]) -> Result<(String, String)> {
+    let separator = data
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or("text has no keyword separator")?;
+
+    Ok((
+        latin1_to_string(&data[..separator]),
+        latin1_to_string(&data[separator + 1..]),
+    ))
+}
+
+/// The fields of an `iTXt` chunk, with the text already inflated if it was
+/// stored compressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: String,
+    pub language: String,
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+/// Parses `iTXt` data: the Latin-1 keyword, a null separator, the compression
+/// flag and method bytes, then the language tag, the translated keyword and
+/// the UTF-8 text, with the first two null-terminated.
+pub fn parse_international_text(data: &[u8]) -> Result<InternationalText> {
+    let separator = data
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or("international text has no keyword separator")?;
+    let keyword = latin1_to_string(&data[..separator]);
+
+    let (flag, method) = match data.get(separator + 1..separator + 3) {
+        Some(&[flag, method]) => (flag, method),
+        _ => Err("international text has no compression flag and method")?,
+    };
+
+    let mut fields = data[separator + 3..].splitn(3, |b| *b == 0);
+    let language = fields.next().unwrap_or_default();
+    let translated_keyword = fields
+        .next()
+        .ok_or("international text has no language tag separator")?;
+    let text = fields
+        .next()
+        .ok_or("international text has no translated keyword separator")?;
+
+    let text = match flag {
+        0 => text.to_vec(),
+        1 => {
+            if method != COMPRESSION_DEFLATE {
+                Err(format!("unsupported compression method {}", method))?;
+            }
+            inflate_text(text)?
+        }
+        _ => Err(format!("invalid compression flag {}", flag))?,
+    };
+
+    Ok(InternationalText {
+        keyword,
+        language: String::from_utf8(language.to_vec())
+            .map_err(|_| "language tag is not valid UTF-8")?,
+        translated_keyword: String::from_utf8(translated_keyword.to_vec())
+            .map_err(|_| "translated keyword is not valid UTF-8")?,
+        text: String::from_utf8(text).map_err(|_| "international text is not valid UTF-8")?,
+    })
+}
+
+/// The keyword and text of a well-formed `tEXt`, `zTXt` or `iTXt` chunk;
+/// `None` for any other chunk, or one whose data doesn't follow the layout.
+pub fn text_entry(chunk: &Chunk) -> Option<(String, String)> {
+    match &chunk.chunk_type().bytes() {
+        b"tEXt" => split_text(chunk.data()).ok(),
+        b"zTXt" => {
+            let (keyword, text) = decompress_text(chunk.data()).ok()?;
+            Some((keyword, latin1_to_string(&text)))
+        }
+        b"iTXt" => {
+            let itxt = parse_international_text(chunk.data()).ok()?;
+            Some((itxt.keyword, itxt.text))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes Latin-1 (ISO 8859-1) bytes, which map one-to-one onto the first
+/// 256 Unicode code points.
+pub fn latin1_to_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// Encodes `text` as Latin-1, refusing characters outside its 256 code points.
+pub fn string_to_latin1(text: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| u8::try_from(c).map_err(|_| format!("'{}' can't be written in Latin-1", c).into()))
+        .collect()
+}
+
+/// Copies UTF-8 text from `reader` to `out` a block at a time, checking it
+/// as it goes so the whole text never has to be held at once. On invalid
+/// UTF-8, the text before it has already been written.
+pub fn copy_utf8(mut reader: impl Read, out: &mut impl Write) -> Result<()> {
+    let mut buf = [0; 8192];
+    // Bytes of a character split across blocks, carried to the next one
+    let mut pending = 0;
+    loop {
+        let read = reader.read(&mut buf[pending..])?;
+        if read == 0 {
+            break;
+        }
+        let filled = pending + read;
+        let valid = match str::from_utf8(&buf[..filled]) {
+            Ok(_) => filled,
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => Err("not valid utf8")?,
+        };
+        out.write_all(&buf[..valid])?;
+        buf.copy_within(valid..filled, 0);
+        pending = filled - valid;
+    }
+    if pending > 0 {
+        Err("not valid utf8")?;
+    }
+    Ok(())
+}
+
+/// Encodes `keyword` as Latin-1 after checking it holds only the printable
+/// characters the PNG specification allows in a keyword (32-126 and 161-255)
+/// and encodes to between 1 and 79 bytes.
+fn encode_keyword(keyword: &str) -> Result<Vec<u8>> {
+    if let Some(c) = keyword
+        .chars()
+        .find(|c| !matches!(*c as u32, 32..=126 | 161..=255))
+    {
+        Err(format!("keyword must be printable Latin-1, not {:?}", c))?;
+    }
+    let bytes = string_to_latin1(keyword)?;
+    if bytes.is_empty() || bytes.len() > 79 {
+        Err("keyword must be between 1 and 79 bytes long")?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let data = compress_text("Comment", b"hello hello hello", COMPRESSION_DEFLATE).unwrap();
+        assert_eq!(&data[..9], b"Comment\0\0");
+
+        let (keyword, text) = decompress_text(&data).unwrap();
+        assert_eq!(keyword, "Comment");
+        assert_eq!(text, b"hello hello hello");
+    }
+
+    #[test]
+    fn test_unsupported_compression_method() {
+        let data = compress_text("Comment", b"hello", 1).unwrap();
+        assert_eq!(data[8], 1);
+
+        let err = decompress_text(&data).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported compression method 1");
+    }
+
+    #[test]
+    fn test_decompress_text_limit() {
+        let text = vec![b'a'; MAX_INFLATED_TEXT as usize];
+        let data = compress_text("Comment", &text, COMPRESSION_DEFLATE).unwrap();
+        assert_eq!(decompress_text(&data).unwrap().1.len(), text.len());
+
+        let text = vec![b'a'; MAX_INFLATED_TEXT as usize + 1];
+        let data = compress_text("Comment", &text, COMPRESSION_DEFLATE).unwrap();
+        let err = decompress_text(&data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "compressed text inflates to more than 16777216 bytes"
+        );
+    }
+
+    #[test]
+    fn test_parse_international_text_limit() {
+        let text = vec![b'a'; MAX_INFLATED_TEXT as usize + 1];
+        let compressed = compress_text("k", &text, COMPRESSION_DEFLATE).unwrap();
+        let mut data = b"Title\0\x01\0en\0\0".to_vec();
+        data.extend_from_slice(&compressed[3..]);
+
+        let err = parse_international_text(&data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "compressed text inflates to more than 16777216 bytes"
+        );
+    }
+
+    #[test]
+    fn test_invalid_keyword() {
+        assert!(compress_text("", b"hello", 0).is_err());
+        assert!(compress_text(&"k".repeat(80), b"hello", 0).is_err());
+        // 79 characters, but 158 bytes of UTF-8
+        assert!(compress_text(&"é".repeat(79), b"hello", 0).is_ok());
+
+        let err = compress_text("Pri\0ce", b"hello", 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "keyword must be printable Latin-1, not '\\0'"
+        );
+        let err = compress_text("5€", b"hello", 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "keyword must be printable Latin-1, not '€'"
+        );
+    }
+
+    #[test]
+    fn test_compress_latin1_keyword_round_trip() {
+        let data = compress_text("Légende", b"hello", COMPRESSION_DEFLATE).unwrap();
+        assert_eq!(&data[..8], b"L\xe9gende\0");
+
+        let (keyword, _) = decompress_text(&data).unwrap();
+        assert_eq!(keyword, "Légende");
+    }
+
+    #[test]
+    fn test_split_text() {
+        let (keyword, text) = split_text(b"Software\0GIMP \xa9 2.10").unwrap();
+        assert_eq!(keyword, "Software");
+        assert_eq!(text, "GIMP © 2.10");
+
+        let (_, text) = split_text(b"Comment\0line one\0line two").unwrap();
+        assert_eq!(text, "line one\0line two");
+
+        assert!(split_text(b"no separator").is_err());
+    }
+
+    #[test]
+    fn test_text_entry() {
+        let ztxt = ChunkType::from_str("zTXt").unwrap();
+        let data = compress_text("Title", b"Caf\xe9 sign", COMPRESSION_DEFLATE).unwrap();
+        let chunk = Chunk::new(ztxt.clone(), data).unwrap();
+        assert_eq!(
+            text_entry(&chunk),
+            Some(("Title".to_string(), "Café sign".to_string()))
+        );
+
+        let chunk = Chunk::new(ztxt, b"Title\0\0not zlib".to_vec()).unwrap();
+        assert_eq!(text_entry(&chunk), None);
+
+        let chunk =
+            Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Author\0me".to_vec()).unwrap();
+        assert_eq!(
+            text_entry(&chunk),
+            Some(("Author".to_string(), "me".to_string()))
+        );
+
+        let chunk =
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"Author\0me".to_vec()).unwrap();
+        assert_eq!(text_entry(&chunk), None);
+    }
+
+    #[test]
+    fn test_parse_international_text() {
+        let data = b"Title\0\0\0de-DE\0Titel\0Gr\xc3\xbc\xc3\x9fe";
+        let itxt = parse_international_text(data).unwrap();
+        assert_eq!(
+            itxt,
+            InternationalText {
+                keyword: "Title".to_string(),
+                language: "de-DE".to_string(),
+                translated_keyword: "Titel".to_string(),
+                text: "Grüße".to_string(),
+            }
+        );
+
+        let chunk = Chunk::new(ChunkType::from_str("iTXt").unwrap(), data.to_vec()).unwrap();
+        assert_eq!(
+            text_entry(&chunk),
+            Some(("Title".to_string(), "Grüße".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_compressed_international_text() {
+        let mut data = b"XML:com.adobe.xmp\0\x01\0\0\0".to_vec();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"<x:xmpmeta/>").unwrap();
+        data.extend(encoder.finish().unwrap());
+
+        let itxt = parse_international_text(&data).unwrap();
+        assert_eq!(itxt.keyword, "XML:com.adobe.xmp");
+        assert_eq!(itxt.language, "");
+        assert_eq!(itxt.text, "<x:xmpmeta/>");
+
+        assert!(parse_international_text(b"Title\0\0\0en").is_err());
+        assert!(parse_international_text(b"Title\0\x02\0\0\0text").is_err());
+    }
+
+    #[test]
+    fn test_latin1_to_string() {
+        assert_eq!(latin1_to_string(&[0x63, 0x61, 0xF1, 0x61]), "caña");
+    }
+
+    #[test]
+    fn test_copy_utf8() {
+        // The first block ends partway through a two-byte character
+        let text = format!("a{}", "ñ".repeat(10_000));
+        let mut out = Vec::new();
+        copy_utf8(text.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, text.as_bytes());
+
+        let mut out = Vec::new();
+        let err = copy_utf8(&b"ok\xff"[..], &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "not valid utf8");
+        assert!(copy_utf8(&b"cut \xc3"[..], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_string_to_latin1() {
+        assert_eq!(string_to_latin1("caña").unwrap(), [0x63, 0x61, 0xF1, 0x61]);
+        let err = string_to_latin1("5€").unwrap_err();
+        assert_eq!(err.to_string(), "'€' can't be written in Latin-1");
+    }
+}
@@ -0,0 +1,363 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::str::FromStr;
+
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+use tempfile::TempDir;
+
+fn write_testing_png(path: &Path) {
+    let chunks = vec![
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]).unwrap(),
+        Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3]).unwrap(),
+        Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap(),
+    ];
+    fs::write(path, Png::from_chunks(chunks).as_bytes()).unwrap();
+}
+
+fn pngme(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_encode_message_from_stdin() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let file = path.to_str().unwrap();
+
+    let output = pngme(
+        &["encode", file, "ruSt", "--message-stdin"],
+        &[0, 1, 2, 255],
+    );
+    assert!(output.status.success());
+
+    let png = Png::try_from(fs::read(&path).unwrap().as_slice()).unwrap();
+    assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), &[0, 1, 2, 255]);
+}
+
+#[test]
+fn test_decode_missing_chunk_type_fails() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+
+    let output = pngme(&["decode", path.to_str().unwrap(), "ruSt"], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: chunk type 'ruSt' not found\n"
+    );
+}
+
+#[test]
+fn test_missing_file_fails() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("missing.png");
+    let file = path.to_str().unwrap();
+
+    let output = pngme(&["print", file], &[]);
+    assert_eq!(output.status.code(), Some(1));
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("error: cannot open {}: no such file\n", file)
+    );
+
+    let output = pngme(&["print", dir.path().to_str().unwrap()], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .ends_with("is a directory, expected a PNG file\n"));
+}
+
+#[test]
+fn test_count_and_verify_name_unopenable_paths() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("missing.png");
+    let file = path.to_str().unwrap();
+    let directory = dir.path().to_str().unwrap();
+
+    for command in ["count", "verify"] {
+        let output = pngme(&[command, file], &[]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("error: cannot open {}: no such file\n", file)
+        );
+
+        let output = pngme(&[command, directory], &[]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!(
+                "error: '{}' is a directory, expected a PNG file\n",
+                directory
+            )
+        );
+    }
+}
+
+#[test]
+fn test_bad_signature_fails() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("text.png");
+    fs::write(&path, "not a png at all").unwrap();
+
+    let output = pngme(&["list", path.to_str().unwrap()], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: invalid header\n"
+    );
+}
+
+#[test]
+fn test_quiet_encode_prints_nothing() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let file = path.to_str().unwrap();
+
+    let output = pngme(&["--quiet", "encode", file, "ruSt", "hello"], &[]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = pngme(&["decode", file, "ruSt", "-q"], &[]);
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[test]
+fn test_verbose_prints_chunk_diagnostics() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+
+    let output = pngme(&["list", path.to_str().unwrap(), "--verbose"], &[]);
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("debug: IHDR at offset 8: length 13, crc "));
+    assert!(stderr.contains("debug: IDAT at offset 33: length 3, crc "));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+}
+
+#[test]
+fn test_decode_raw_writes_bytes_unchanged() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let file = path.to_str().unwrap();
+    let payload = [0x89, 0x00, 0xc3, 0x28, 0x0a];
+
+    let output = pngme(&["encode", file, "ruSt", "--message-stdin"], &payload);
+    assert!(output.status.success());
+
+    let output = pngme(&["decode", file, "ruSt", "--raw"], &[]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, payload);
+
+    let output = pngme(&["decode", file, "ruSt", "--raw", "--base64"], &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_encode_same_type_twice_warns() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let file = path.to_str().unwrap();
+
+    let output = pngme(&["encode", file, "ruSt", "first"], &[]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let output = pngme(&["encode", file, "ruSt", "second"], &[]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: the file already has a ruSt chunk; this adds a duplicate and decode will \
+         still read the first one (use --replace to overwrite it)\n"
+    );
+
+    let output = pngme(&["encode", file, "ruSt", "third", "--replace"], &[]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_list_and_info_uncolored_when_piped() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let file = path.to_str().unwrap();
+
+    for command in ["list", "info"] {
+        let output = pngme(&[command, file], &[]);
+        assert!(output.status.success());
+        assert!(!output.stdout.is_empty());
+        assert!(!output.stdout.contains(&0x1b));
+    }
+
+    let output = pngme(&["list", file, "--color", "always"], &[]);
+    assert!(output.stdout.contains(&0x1b));
+}
+
+#[test]
+fn test_duplicate_iend_rejected_or_warned() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let mut bytes = fs::read(&path).unwrap();
+    let iend = Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]).unwrap();
+    bytes.extend_from_slice(&iend.as_bytes());
+    fs::write(&path, &bytes).unwrap();
+    let file = path.to_str().unwrap();
+
+    let output = pngme(&["print", file], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("duplicate chunks: IEND at offset 0x3c"));
+
+    let output = pngme(&["list", file], &[]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: duplicate IEND chunk at offset 0x3c\n"
+    );
+}
+
+#[test]
+fn test_verify_check_only_exit_codes() {
+    let dir = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a.png", "b.png"]
+        .iter()
+        .map(|name| {
+            let path = dir.path().join(name);
+            write_testing_png(&path);
+            path.to_str().unwrap().to_string()
+        })
+        .collect();
+    fs::write(dir.path().join("notes.txt"), "not a png").unwrap();
+    let directory = dir.path().to_str().unwrap();
+
+    let output = pngme(&["verify", "--check-only", directory], &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "pass {}\npass {}\nsummary: 2 passed, 0 failed, 0 unreadable\n",
+            paths[0], paths[1]
+        )
+    );
+
+    // Flip the last byte of the IDAT chunk's CRC
+    let mut bytes = fs::read(&paths[1]).unwrap();
+    bytes[47] ^= 0xff;
+    fs::write(&paths[1], &bytes).unwrap();
+    let output = pngme(&["verify", "--check-only", directory], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("\nfail {}: IDAT stored ", paths[1])));
+    assert!(stdout.ends_with("summary: 1 passed, 1 failed, 0 unreadable\n"));
+
+    let missing = dir.path().join("missing.png");
+    let args = [
+        "verify",
+        "--check-only",
+        directory,
+        missing.to_str().unwrap(),
+    ];
+    let output = pngme(&args, &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("summary: 1 passed, 1 failed, 1 unreadable\n"));
+}
+
+#[test]
+fn test_pipe_through_encode_and_remove() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let original = fs::read(&path).unwrap();
+
+    let output = pngme(&["encode", "-", "ruSt", "piped"], &original);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "file will grow by 17 bytes\n"
+    );
+    let encoded = output.stdout;
+    let png = Png::try_from(encoded.as_slice()).unwrap();
+    assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"piped");
+
+    let output = pngme(&["decode", "-", "ruSt"], &encoded);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"piped\n");
+
+    let output = pngme(&["print", "-"], &encoded);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("ruSt"));
+
+    let output = pngme(&["remove", "-", "ruSt"], &encoded);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, original);
+
+    let output = pngme(&["encode", "-", "ruSt", "--message-stdin"], &original);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: the PNG and the message can't both come from standard input\n"
+    );
+}
+
+#[test]
+fn test_pipe_through_strip_relabel_and_insert() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test.png");
+    write_testing_png(&path);
+    let original = fs::read(&path).unwrap();
+    let encoded = pngme(&["encode", "-", "tEXt", "piped"], &original).stdout;
+
+    let output = pngme(&["relabel", "-", "tEXt", "ruSt"], &encoded);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "relabelled 1 tEXt chunks as ruSt\n"
+    );
+    let relabelled = Png::try_from(output.stdout.as_slice()).unwrap();
+    assert_eq!(relabelled.chunk_by_type("ruSt").unwrap().data(), b"piped");
+
+    let output = pngme(&["strip", "-"], &encoded);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "removed 1 chunks (17 bytes)\n"
+    );
+    assert_eq!(output.stdout, original);
+
+    let output = pngme(&["insert", "-", "ruSt", "piped", "--index", "1"], &original);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let inserted = Png::try_from(output.stdout.as_slice()).unwrap();
+    assert_eq!(inserted.chunks()[1].data(), b"piped");
+    assert!(!dir.path().join("-").exists());
+    assert!(!Path::new("-").exists());
+}
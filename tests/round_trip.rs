@@ -0,0 +1,53 @@
+use std::fs;
+use std::str::FromStr;
+
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+/// A 4x4 palette image with tRNS and tEXt chunks and its image data split
+/// over two IDAT chunks.
+const FIXTURE: &str = "tests/fixtures/palette.png";
+
+fn idat_bytes(png: &Png) -> Vec<Vec<u8>> {
+    png.chunks_by_type("IDAT").map(|c| c.as_bytes()).collect()
+}
+
+#[test]
+fn test_fixture_round_trips_byte_for_byte() {
+    let bytes = fs::read(FIXTURE).unwrap();
+    let png = Png::from_bytes(&bytes).unwrap();
+    assert_eq!(png.as_bytes(), bytes);
+    assert_eq!(Png::from_file(FIXTURE).unwrap().as_bytes(), bytes);
+}
+
+#[test]
+fn test_metadata_edits_keep_idat() {
+    let bytes = fs::read(FIXTURE).unwrap();
+    let mut png = Png::from_bytes(&bytes).unwrap();
+    let idat = idat_bytes(&png);
+    assert_eq!(idat.len(), 2);
+
+    png.encode("ruSt", "hidden message").unwrap();
+    assert_eq!(idat_bytes(&png), idat);
+
+    let text = ChunkType::from_str("tEXt").unwrap();
+    assert_eq!(png.remove_chunks_of_type(&text).len(), 1);
+    assert_eq!(idat_bytes(&png), idat);
+
+    png.strip_ancillary(&[]);
+    assert_eq!(idat_bytes(&png), idat);
+
+    let reparsed = Png::from_bytes(&png.as_bytes()).unwrap();
+    assert_eq!(idat_bytes(&reparsed), idat);
+}
+
+#[test]
+fn test_encode_then_remove_restores_file() {
+    let bytes = fs::read(FIXTURE).unwrap();
+    let mut png = Png::from_bytes(&bytes).unwrap();
+    png.encode("ruSt", "hidden message").unwrap();
+
+    let ru_st = ChunkType::from_str("ruSt").unwrap();
+    png.remove_chunks_of_type(&ru_st);
+    assert_eq!(png.as_bytes(), bytes);
+}